@@ -0,0 +1,172 @@
+//! ``alarm`` module of ``luma_core``.
+//!
+//! Contains one-shot and periodic software alarms, multiplexed onto the decrementer.
+//!
+//! Pending alarms are kept in a queue sorted by deadline, and the decrementer is always
+//! programmed to expire at the earliest one.  Callbacks run from the decrementer exception, with
+//! interrupts disabled, so they should be short and must not block.
+//!
+//! **NOTE**: Alarms only fire while interrupts are enabled, see ``processor::cpu_isr_enable``.
+
+use crate::processor;
+use crate::register::mtdec;
+use crate::time::{duration_to_ticks, timebase, Instant};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::time::Duration;
+
+/// Largest value the decrementer can count down from without immediately firing.
+const DECREMENTER_MAX: u64 = 0x7fff_ffff;
+
+/// Identifier of a pending alarm, used to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AlarmId(u32);
+
+struct Alarm {
+    id: AlarmId,
+    deadline: u64,
+    period: u64,
+    callback: Box<dyn FnMut() + Send>,
+}
+
+struct Queue {
+    /// Pending alarms, sorted by deadline.
+    alarms: Vec<Alarm>,
+    next_id: u32,
+    /// Alarm whose callback is currently running, and whether it got cancelled meanwhile.
+    running: Option<(AlarmId, bool)>,
+}
+
+impl Queue {
+    fn insert(&mut self, alarm: Alarm) {
+        let index = self
+            .alarms
+            .iter()
+            .position(|pending| pending.deadline > alarm.deadline)
+            .unwrap_or(self.alarms.len());
+        self.alarms.insert(index, alarm);
+    }
+
+    /// Program the decrementer to expire at the earliest deadline.
+    fn reprogram(&self) {
+        let ticks = match self.alarms.first() {
+            Some(alarm) => alarm.deadline.saturating_sub(timebase()),
+            None => DECREMENTER_MAX,
+        };
+        mtdec(ticks.clamp(1, DECREMENTER_MAX) as u32);
+    }
+}
+
+/// The global alarm queue, only ever accessed with interrupts disabled.
+struct AlarmQueue(UnsafeCell<Queue>);
+
+unsafe impl Sync for AlarmQueue {}
+
+impl AlarmQueue {
+    fn with<R>(&self, f: impl FnOnce(&mut Queue) -> R) -> R {
+        processor::critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+}
+
+static QUEUE: AlarmQueue = AlarmQueue(UnsafeCell::new(Queue {
+    alarms: Vec::new(),
+    next_id: 0,
+    running: None,
+}));
+
+fn schedule(deadline: u64, period: u64, callback: Box<dyn FnMut() + Send>) -> AlarmId {
+    QUEUE.with(|queue| {
+        let id = AlarmId(queue.next_id);
+        queue.next_id = queue.next_id.wrapping_add(1);
+        queue.insert(Alarm {
+            id,
+            deadline,
+            period,
+            callback,
+        });
+        queue.reprogram();
+        id
+    })
+}
+
+/// Call ``callback`` once, after ``timeout`` has elapsed.
+pub fn set_alarm(timeout: Duration, callback: impl FnMut() + Send + 'static) -> AlarmId {
+    let deadline = timebase().saturating_add(duration_to_ticks(timeout));
+    schedule(deadline, 0, Box::new(callback))
+}
+
+/// Call ``callback`` once, when ``deadline`` is reached.
+pub fn set_alarm_at(deadline: Instant, callback: impl FnMut() + Send + 'static) -> AlarmId {
+    schedule(deadline.ticks(), 0, Box::new(callback))
+}
+
+/// Call ``callback`` after ``start`` has elapsed, then every ``period``.
+///
+/// **NOTE**: This function panics if ``period`` is zero.
+pub fn set_periodic_alarm(
+    start: Duration,
+    period: Duration,
+    callback: impl FnMut() + Send + 'static,
+) -> AlarmId {
+    let period = duration_to_ticks(period);
+    assert!(period > 0, "An alarm period can't be zero");
+    let deadline = timebase().saturating_add(duration_to_ticks(start));
+    schedule(deadline, period, Box::new(callback))
+}
+
+/// Cancel a pending alarm, returns whether it was still pending.
+///
+/// A periodic alarm may be cancelled from its own callback.
+pub fn cancel_alarm(id: AlarmId) -> bool {
+    QUEUE.with(|queue| {
+        if let Some(index) = queue.alarms.iter().position(|alarm| alarm.id == id) {
+            queue.alarms.remove(index);
+            queue.reprogram();
+            true
+        } else {
+            match queue.running {
+                Some((running, ref mut cancelled)) if running == id && !*cancelled => {
+                    *cancelled = true;
+                    true
+                }
+                _ => false,
+            }
+        }
+    })
+}
+
+/// Run every expired alarm and program the decrementer for the next one.
+///
+/// Called from the decrementer exception.
+pub(crate) fn decrementer_handler() {
+    loop {
+        let now = timebase();
+        let expired = QUEUE.with(|queue| match queue.alarms.first() {
+            Some(alarm) if alarm.deadline <= now => {
+                let alarm = queue.alarms.remove(0);
+                queue.running = Some((alarm.id, false));
+                Some(alarm)
+            }
+            _ => None,
+        });
+
+        let mut alarm = match expired {
+            Some(alarm) => alarm,
+            None => break,
+        };
+
+        // The queue is released while the callback runs, so that it can set or cancel alarms.
+        (alarm.callback)();
+
+        QUEUE.with(|queue| {
+            let cancelled = matches!(queue.running.take(), Some((_, true)));
+            if alarm.period != 0 && !cancelled {
+                alarm.deadline += alarm.period;
+                queue.insert(alarm);
+            }
+        });
+    }
+
+    QUEUE.with(|queue| queue.reprogram());
+}
@@ -0,0 +1,464 @@
+//! ``bat`` module of ``luma_core``.
+//!
+//! Contains the block address translation registers, which map the effective addresses to
+//! physical memory.
+//!
+//! Broadway has eight instruction BATs and eight data BATs, each mapping a power of two block
+//! from 128 KiB to 256 MiB.  BATs 4 to 7 are only used while HID4[SBE] is set, which the runtime
+//! does at boot.  The segment registers are left invalid, so any access outside of the BATs
+//! raises an ISI or DSI exception.
+
+use crate::processor;
+use crate::spr::{self, Spr};
+
+/// The size of a block mapped by a BAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockLength {
+    /// 128 KiB.
+    Kib128,
+    /// 256 KiB.
+    Kib256,
+    /// 512 KiB.
+    Kib512,
+    /// 1 MiB.
+    Mib1,
+    /// 2 MiB.
+    Mib2,
+    /// 4 MiB.
+    Mib4,
+    /// 8 MiB.
+    Mib8,
+    /// 16 MiB.
+    Mib16,
+    /// 32 MiB.
+    Mib32,
+    /// 64 MiB.
+    Mib64,
+    /// 128 MiB.
+    Mib128,
+    /// 256 MiB.
+    Mib256,
+}
+
+impl BlockLength {
+    /// Get the BL field encoding this length, a mask of the effective address bits ignored.
+    pub const fn bits(self) -> u32 {
+        (1 << (self as u32)) - 1
+    }
+
+    /// Get the length for a BL field, the invalid encodings have none.
+    pub fn from_bits(bits: u32) -> Option<BlockLength> {
+        use BlockLength::*;
+        [
+            Kib128, Kib256, Kib512, Mib1, Mib2, Mib4, Mib8, Mib16, Mib32, Mib64, Mib128, Mib256,
+        ]
+        .iter()
+        .copied()
+        .find(|length| length.bits() == bits)
+    }
+
+    /// Get this length in bytes.
+    pub const fn size(self) -> u32 {
+        0x2_0000 << (self as u32)
+    }
+}
+
+bitflags::bitflags! {
+    /// The storage attributes of a block.
+    pub struct Wimg: u32 {
+        /// Write-through, stores update memory as well as the cache.
+        const WRITE_THROUGH = 0b1000;
+        /// Caching-inhibited.
+        const CACHE_INHIBITED = 0b0100;
+        /// Memory coherence is enforced on the bus.
+        const MEMORY_COHERENT = 0b0010;
+        /// Guarded, no speculative or out-of-order accesses.  Data BATs only.
+        const GUARDED = 0b0001;
+        /// Caching-inhibited and guarded, the attributes of hardware registers.
+        const UNCACHED = 0b0101;
+    }
+}
+
+/// The access allowed to a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protection {
+    /// No access, which still lets the BAT take precedence over the segment registers.
+    NoAccess,
+    /// Read-only.
+    ReadOnly,
+    /// Read and write.
+    ReadWrite,
+}
+
+impl Protection {
+    /// Get the PP field encoding this protection.
+    pub const fn bits(self) -> u32 {
+        match self {
+            Protection::NoAccess => 0b00,
+            Protection::ReadOnly => 0b01,
+            Protection::ReadWrite => 0b10,
+        }
+    }
+
+    /// Get the protection for a PP field, both read-only encodings giving ``ReadOnly``.
+    pub const fn from_bits(bits: u32) -> Protection {
+        match bits & 0b11 {
+            0b00 => Protection::NoAccess,
+            0b10 => Protection::ReadWrite,
+            _ => Protection::ReadOnly,
+        }
+    }
+}
+
+/// A block address translation, mapping a block of effective addresses to physical memory.
+///
+/// **NOTE**: Both addresses are truncated to a multiple of 128 KiB, and ``write`` panics unless
+/// they are aligned on the block length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bat {
+    effective: u32,
+    physical: u32,
+    length: BlockLength,
+    wimg: Wimg,
+    supervisor: bool,
+    user: bool,
+    protection: Protection,
+}
+
+impl Bat {
+    /// Create a cached read-write translation, valid in supervisor and user mode.
+    pub const fn new(effective: u32, physical: u32, length: BlockLength) -> Bat {
+        Bat {
+            effective: effective & 0xfffe_0000,
+            physical: physical & 0xfffe_0000,
+            length,
+            wimg: Wimg::empty(),
+            supervisor: true,
+            user: true,
+            protection: Protection::ReadWrite,
+        }
+    }
+
+    /// Set the storage attributes of this translation.
+    pub const fn wimg(self, wimg: Wimg) -> Bat {
+        Bat { wimg, ..self }
+    }
+
+    /// Set the access allowed through this translation.
+    pub const fn protection(self, protection: Protection) -> Bat {
+        Bat { protection, ..self }
+    }
+
+    /// Set the modes in which this translation is valid (Vs and Vp).
+    pub const fn valid_in(self, supervisor: bool, user: bool) -> Bat {
+        Bat {
+            supervisor,
+            user,
+            ..self
+        }
+    }
+
+    /// Get the first effective address of the block (BEPI).
+    pub const fn effective(&self) -> u32 {
+        self.effective
+    }
+
+    /// Get the first physical address of the block (BRPN).
+    pub const fn physical(&self) -> u32 {
+        self.physical
+    }
+
+    /// Get the length of the block (BL).
+    pub const fn length(&self) -> BlockLength {
+        self.length
+    }
+
+    /// Get the storage attributes (WIMG).
+    pub const fn storage(&self) -> Wimg {
+        self.wimg
+    }
+
+    /// Get the access allowed (PP).
+    pub const fn access(&self) -> Protection {
+        self.protection
+    }
+
+    /// Whether this translation is valid in supervisor mode (Vs).
+    pub const fn supervisor(&self) -> bool {
+        self.supervisor
+    }
+
+    /// Whether this translation is valid in user mode (Vp).
+    pub const fn user(&self) -> bool {
+        self.user
+    }
+
+    /// Whether an effective address falls in this block.
+    pub const fn contains(&self, address: u32) -> bool {
+        address.wrapping_sub(self.effective) < self.length.size()
+    }
+
+    /// Translate an effective address of this block to its physical address.
+    pub fn translate(&self, address: u32) -> Option<u32> {
+        if self.contains(address) {
+            Some(self.physical + (address - self.effective))
+        } else {
+            None
+        }
+    }
+
+    /// Get the value of the upper BAT register.
+    pub const fn upper(&self) -> u32 {
+        self.effective
+            | (self.length.bits() << 2)
+            | ((self.supervisor as u32) << 1)
+            | (self.user as u32)
+    }
+
+    /// Get the value of the lower BAT register.
+    pub const fn lower(&self) -> u32 {
+        self.physical | (self.wimg.bits() << 3) | self.protection.bits()
+    }
+
+    /// Decode the values of a BAT register pair, ``None`` if the BAT is invalid in both modes
+    /// or its block length isn't a valid encoding.
+    pub fn from_raw(upper: u32, lower: u32) -> Option<Bat> {
+        if upper & 0b11 == 0 {
+            return None;
+        }
+        Some(Bat {
+            effective: upper & 0xfffe_0000,
+            physical: lower & 0xfffe_0000,
+            length: BlockLength::from_bits((upper >> 2) & 0x7ff)?,
+            wimg: Wimg::from_bits_truncate(lower >> 3),
+            supervisor: upper & 0b10 != 0,
+            user: upper & 0b01 != 0,
+            protection: Protection::from_bits(lower),
+        })
+    }
+
+    /// Check that both addresses are aligned on the block length.
+    fn check(&self) {
+        let mask = self.length.size() - 1;
+        assert!(
+            self.effective & mask == 0 && self.physical & mask == 0,
+            "BAT addresses must be aligned on the block length"
+        );
+    }
+}
+
+/// The two kinds of BATs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Instruction BATs, used for instruction fetches.
+    Instruction,
+    /// Data BATs, used for loads and stores.
+    Data,
+}
+
+/// Get the SPRs of the upper and lower halves of a BAT.
+fn sprs(kind: Kind, index: usize) -> (Spr, Spr) {
+    match (kind, index) {
+        (Kind::Instruction, 0) => (Spr::Ibat0U, Spr::Ibat0L),
+        (Kind::Instruction, 1) => (Spr::Ibat1U, Spr::Ibat1L),
+        (Kind::Instruction, 2) => (Spr::Ibat2U, Spr::Ibat2L),
+        (Kind::Instruction, 3) => (Spr::Ibat3U, Spr::Ibat3L),
+        (Kind::Instruction, 4) => (Spr::Ibat4U, Spr::Ibat4L),
+        (Kind::Instruction, 5) => (Spr::Ibat5U, Spr::Ibat5L),
+        (Kind::Instruction, 6) => (Spr::Ibat6U, Spr::Ibat6L),
+        (Kind::Instruction, 7) => (Spr::Ibat7U, Spr::Ibat7L),
+        (Kind::Data, 0) => (Spr::Dbat0U, Spr::Dbat0L),
+        (Kind::Data, 1) => (Spr::Dbat1U, Spr::Dbat1L),
+        (Kind::Data, 2) => (Spr::Dbat2U, Spr::Dbat2L),
+        (Kind::Data, 3) => (Spr::Dbat3U, Spr::Dbat3L),
+        (Kind::Data, 4) => (Spr::Dbat4U, Spr::Dbat4L),
+        (Kind::Data, 5) => (Spr::Dbat5U, Spr::Dbat5L),
+        (Kind::Data, 6) => (Spr::Dbat6U, Spr::Dbat6L),
+        (Kind::Data, 7) => (Spr::Dbat7U, Spr::Dbat7L),
+        _ => panic!("There are only eight BATs of each kind"),
+    }
+}
+
+/// Read the given BAT, ``None`` if it is invalid.
+pub fn read(kind: Kind, index: usize) -> Option<Bat> {
+    let (upper, lower) = sprs(kind, index);
+    Bat::from_raw(spr::read(upper), spr::read(lower))
+}
+
+/// Program the given BAT, or invalidate it with ``None``.
+///
+/// **NOTE**: This function panics if the addresses of ``bat`` aren't aligned on its length.
+///
+/// # Safety
+/// The code, stack and data currently in use **MUST** stay mapped, at the same addresses and
+/// with compatible attributes, and no two BATs of the same kind may overlap.
+pub unsafe fn write(kind: Kind, index: usize, bat: Option<Bat>) {
+    // Leave an unchanged BAT alone, it may be the one mapping this very code.
+    if read(kind, index) == bat {
+        return;
+    }
+
+    let (upper, lower) = sprs(kind, index);
+    match bat {
+        Some(bat) => {
+            bat.check();
+            spr::write(lower, bat.lower());
+            spr::write(upper, bat.upper());
+        }
+        None => spr::write(upper, 0),
+    }
+    processor::ppc_isync();
+}
+
+/// The mapping of every BAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BatConfig {
+    /// Instruction BATs 0 to 7.
+    pub instruction: [Option<Bat>; 8],
+    /// Data BATs 0 to 7.
+    pub data: [Option<Bat>; 8],
+}
+
+/// MEM1, cached, at ``0x8000_0000``.
+const MEM1_CACHED: Bat = Bat::new(0x8000_0000, 0x0000_0000, BlockLength::Mib256);
+
+/// MEM2, cached, at ``0x9000_0000``.
+const MEM2_CACHED: Bat = Bat::new(0x9000_0000, 0x1000_0000, BlockLength::Mib256);
+
+/// MEM1 and the hardware registers, uncached, at ``0xc000_0000``.
+const MEM1_UNCACHED: Bat =
+    Bat::new(0xc000_0000, 0x0000_0000, BlockLength::Mib256).wimg(Wimg::UNCACHED);
+
+/// MEM2 and the Hollywood registers, uncached, at ``0xd000_0000``.
+const MEM2_UNCACHED: Bat =
+    Bat::new(0xd000_0000, 0x1000_0000, BlockLength::Mib256).wimg(Wimg::UNCACHED);
+
+impl BatConfig {
+    /// The mapping set up at boot by the runtime: MEM1 and MEM2 cached at ``0x8000_0000`` and
+    /// ``0x9000_0000`` for instructions and data, and uncached at ``0xc000_0000`` and
+    /// ``0xd000_0000`` for data.
+    pub const BOOT: BatConfig = BatConfig {
+        instruction: [
+            Some(MEM1_CACHED),
+            None,
+            None,
+            None,
+            Some(MEM2_CACHED),
+            None,
+            None,
+            None,
+        ],
+        data: [
+            Some(MEM1_CACHED),
+            Some(MEM1_UNCACHED),
+            None,
+            None,
+            Some(MEM2_CACHED),
+            Some(MEM2_UNCACHED),
+            None,
+            None,
+        ],
+    };
+
+    /// Read the current mapping of every BAT.
+    pub fn current() -> BatConfig {
+        let mut config = BatConfig {
+            instruction: [None; 8],
+            data: [None; 8],
+        };
+        for index in 0..8 {
+            config.instruction[index] = read(Kind::Instruction, index);
+            config.data[index] = read(Kind::Data, index);
+        }
+        config
+    }
+
+    /// Get the upper and lower register values of instruction BATs 0 to 7 then data BATs 0 to
+    /// 7, the table the runtime programs at boot.
+    pub const fn raw(&self) -> [[u32; 2]; 16] {
+        let mut raw = [[0; 2]; 16];
+        let mut index = 0;
+        while index < 8 {
+            if let Some(bat) = self.instruction[index] {
+                raw[index] = [bat.upper(), bat.lower()];
+            }
+            if let Some(bat) = self.data[index] {
+                raw[8 + index] = [bat.upper(), bat.lower()];
+            }
+            index += 1;
+        }
+        raw
+    }
+
+    /// Find the data BAT translating an effective address.
+    pub fn find_data(&self, address: u32) -> Option<&Bat> {
+        self.data.iter().flatten().find(|bat| bat.contains(address))
+    }
+
+    /// Program every BAT with this mapping.
+    ///
+    /// **NOTE**: This function panics if the addresses of a BAT aren't aligned on its length.
+    ///
+    /// # Safety
+    /// See ``write``, this mapping **MUST** keep the code, stack and data in use mapped.
+    pub unsafe fn apply(&self) {
+        for index in 0..8 {
+            write(Kind::Instruction, index, self.instruction[index]);
+            write(Kind::Data, index, self.data[index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_raw_values() {
+        let mut expected = [[0; 2]; 16];
+        // IBAT0 and IBAT4.
+        expected[0] = [0x8000_1fff, 0x0000_0002];
+        expected[4] = [0x9000_1fff, 0x1000_0002];
+        // DBAT0, DBAT1, DBAT4 and DBAT5.
+        expected[8] = [0x8000_1fff, 0x0000_0002];
+        expected[9] = [0xc000_1fff, 0x0000_002a];
+        expected[12] = [0x9000_1fff, 0x1000_0002];
+        expected[13] = [0xd000_1fff, 0x1000_002a];
+        assert_eq!(BatConfig::BOOT.raw(), expected);
+    }
+
+    #[test]
+    fn block_length_bits() {
+        assert_eq!(BlockLength::Kib128.bits(), 0x000);
+        assert_eq!(BlockLength::Mib1.bits(), 0x007);
+        assert_eq!(BlockLength::Mib256.bits(), 0x7ff);
+        assert_eq!(BlockLength::from_bits(0x7ff), Some(BlockLength::Mib256));
+        assert_eq!(BlockLength::from_bits(0x005), None);
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let bats = [
+            Bat::new(0x8000_0000, 0x0000_0000, BlockLength::Mib256),
+            Bat::new(0xc000_0000, 0x0000_0000, BlockLength::Mib256).wimg(Wimg::UNCACHED),
+            Bat::new(0xe000_0000, 0x0120_0000, BlockLength::Kib128)
+                .wimg(Wimg::WRITE_THROUGH | Wimg::MEMORY_COHERENT)
+                .protection(Protection::ReadOnly)
+                .valid_in(true, false),
+            Bat::new(0x0080_0000, 0x0080_0000, BlockLength::Mib8)
+                .protection(Protection::NoAccess)
+                .valid_in(false, true),
+        ];
+        for bat in bats.iter() {
+            assert_eq!(Bat::from_raw(bat.upper(), bat.lower()), Some(*bat));
+        }
+    }
+
+    #[test]
+    fn from_raw_invalid() {
+        // Invalid in both modes.
+        assert_eq!(Bat::from_raw(0x8000_1ffc, 0x0000_0002), None);
+        // Not a block length.
+        assert_eq!(Bat::from_raw(0x8000_0017, 0x0000_0002), None);
+    }
+}
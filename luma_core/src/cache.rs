@@ -2,8 +2,12 @@
 //!
 //! Contains functions for the L1, L2, Data and Instruction caches.
 
-use crate::{mfspr, mtspr, processor};
+use crate::processor;
+use crate::spr::{self, Hid2, Hid4, Spr};
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
 
+#[cfg(target_arch = "powerpc")]
 global_asm!(include_str!("../asm/cache.S"));
 
 // Load cache functions from global assembly.
@@ -91,6 +95,23 @@ extern "C" {
     /// * The length of the range to invalidate should be a multiple of 32.
     pub fn DCStoreRangeNS(start_address: *const u32, length: u32);
 
+    /// Enable the locked cache.
+    ///
+    /// Writes back the L1 d(ata)-cache, sets HID2[LCE] so that half of it
+    /// becomes scratchpad RAM, maps it at ``0xE0000000`` through DBAT3
+    /// and allocates every block of it with ``dcbz_l``.
+    ///
+    /// **NOTE**: Interrupts are disabled while this function runs.
+    pub fn LCEnable();
+
+    /// Disable the locked cache.
+    ///
+    /// Invalidates every block of the scratchpad and clears HID2[LCE],
+    /// returning the whole L1 d(ata)-cache to normal operation.
+    ///
+    /// **NOTE**: This function calls ``sync`` after disabling.
+    pub fn LCDisable();
+
     /// Enable the L1 i(nstruction)-cache.
     ///
     /// **NOTE**: This function is preceded by ``isync`` when enabling.
@@ -161,17 +182,16 @@ extern "C" {
 ///    - L2FM=01 (64-byte fetch mode)
 ///    - BCO=1 (dual 64-byte castout buffers)
 ///    - L2MUM=1 (configured as 2-deep miss-under-miss cache)
-#[allow(non_snake_case, unused_unsafe)]
+#[allow(non_snake_case)]
 pub fn L2Enhance() {
-    // Disable the CPU ISR
-    let level = 0u32;
-    processor::cpu_isr_disable(level);
+    // Disable the CPU ISR until the function returns.
+    let _guard = processor::InterruptGuard::new();
 
     // Load the value from the HID4 register.
-    let mut hid4_value = mfspr!(HID4);
+    let mut hid4 = Hid4::read();
 
     // Make sure the H4A is set before enhancing.
-    if (hid4_value & 0x80000000) != 0 {
+    if hid4.contains(Hid4::H4A) {
         unsafe {
             // There is no simple way to flush only the L2 cache.
             DCFlushRangeNS(0x80000000 as *const u32, 0x01800000);
@@ -181,17 +201,183 @@ pub fn L2Enhance() {
             L2Invalidate();
 
             // Set bits for L2 enhancing
-            // L2FM=01 BCO=1 L2MUM=1
-            hid4_value |= 0x24200000;
+            hid4.remove(Hid4::L2FM);
+            hid4.insert(Hid4::L2FM_64 | Hid4::BCO | Hid4::L2MUM);
 
             // Write the current HID4 value to the register.
-            mtspr!(hid4_value, HID4);
+            hid4.write();
 
             // Re-enable the L2 cache.
             L2Enable();
         }
     }
+}
+
+/// Base address of the locked cache scratchpad.
+pub const LOCKED_CACHE_BASE: usize = 0xe000_0000;
+
+/// Size of the locked cache scratchpad, half of the L1 d(ata)-cache.
+pub const LOCKED_CACHE_SIZE: usize = 16 * 1024;
+
+/// Size of a cache block, the unit of every locked cache DMA transfer.
+pub const CACHE_BLOCK_SIZE: usize = 32;
+
+/// Number of DMA commands the locked cache queue can hold.
+pub const LOCKED_CACHE_QUEUE_DEPTH: u32 = 15;
+
+/// Maximum number of blocks a single DMA command can move.
+const DMA_MAX_BLOCKS: usize = 128;
+
+/// Whether a ``LockedCache`` currently exists.
+static LOCKED_CACHE_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// The 16 KiB of L1 d(ata)-cache used as scratchpad RAM while the locked cache is enabled.
+#[repr(C, align(32))]
+pub struct Scratchpad([u8; LOCKED_CACHE_SIZE]);
+
+impl Scratchpad {
+    /// Return the raw pointer to the scratchpad.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr()
+    }
+
+    /// Return the raw mutable pointer to the scratchpad.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.0.as_mut_ptr()
+    }
+}
+
+impl Deref for Scratchpad {
+    type Target = [u8; LOCKED_CACHE_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Scratchpad {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A struct representing the locked cache, in which half of the L1 d(ata)-cache is used as
+/// scratchpad RAM at ``0xE0000000``.  Data is moved between it and main memory by DMA, which runs
+/// in the background and is tracked through the DMA queue length.
+///
+/// The locked cache is disabled again once this struct is dropped.
+pub struct LockedCache {
+    _private: (),
+}
+
+impl LockedCache {
+    /// Enable the locked cache.
+    ///
+    /// **NOTE**: This function panics if the locked cache is already enabled.
+    pub fn enable() -> LockedCache {
+        if LOCKED_CACHE_TAKEN.swap(true, Ordering::Acquire) {
+            panic!("The locked cache is already enabled");
+        }
+
+        unsafe { LCEnable() };
+        LockedCache { _private: () }
+    }
+
+    /// Get a reference to the scratchpad.
+    pub fn scratchpad(&self) -> &Scratchpad {
+        unsafe { &*(LOCKED_CACHE_BASE as *const Scratchpad) }
+    }
+
+    /// Get a mutable reference to the scratchpad.
+    pub fn scratchpad_mut(&mut self) -> &mut Scratchpad {
+        unsafe { &mut *(LOCKED_CACHE_BASE as *mut Scratchpad) }
+    }
+
+    /// Queue a DMA transfer of ``blocks`` cache blocks from main memory into the scratchpad.
+    ///
+    /// Transfers longer than 128 blocks are split into several DMA commands, waiting for room in
+    /// the queue as needed.  The function returns as soon as the last command has been queued,
+    /// use ``queue_length`` or ``queue_wait`` to know when it has completed.
+    ///
+    /// # Safety
+    /// * ``dst`` **MUST** point into the scratchpad and ``src`` into main memory.
+    /// * Both addresses **MUST** be aligned on a 32 byte boundary.
+    /// * ``src`` should have been flushed from the d(ata)-cache with ``DCFlushRange``.
+    /// * The scratchpad range **MUST NOT** be accessed until the transfer has completed.
+    pub unsafe fn load_blocks(&mut self, dst: *mut u8, src: *const u8, blocks: usize) {
+        self.transfer(dst as usize, src as usize, blocks, true);
+    }
+
+    /// Queue a DMA transfer of ``blocks`` cache blocks from the scratchpad to main memory.
+    ///
+    /// Transfers longer than 128 blocks are split into several DMA commands, waiting for room in
+    /// the queue as needed.  The function returns as soon as the last command has been queued,
+    /// use ``queue_length`` or ``queue_wait`` to know when it has completed.
+    ///
+    /// # Safety
+    /// * ``dst`` **MUST** point into main memory and ``src`` into the scratchpad.
+    /// * Both addresses **MUST** be aligned on a 32 byte boundary.
+    /// * ``dst`` should be invalidated in the d(ata)-cache with ``DCInvalidateRange``.
+    /// * The memory range **MUST NOT** be accessed until the transfer has completed.
+    pub unsafe fn store_blocks(&mut self, dst: *mut u8, src: *const u8, blocks: usize) {
+        self.transfer(src as usize, dst as usize, blocks, false);
+    }
+
+    /// Get the number of DMA commands still pending in the queue.
+    pub fn queue_length(&self) -> u32 {
+        Hid2::read().dma_queue_length()
+    }
+
+    /// Wait until at most ``length`` DMA commands are pending in the queue.
+    pub fn queue_wait(&self, length: u32) {
+        while self.queue_length() > length {
+            processor::ppc_nop();
+        }
+    }
+
+    /// Discard every DMA command pending in the queue.
+    pub fn queue_flush(&mut self) {
+        // DMAL[F]
+        spr::write(Spr::Dmal, 0x0000_0001);
+    }
+
+    fn transfer(&mut self, lc_addr: usize, mem_addr: usize, blocks: usize, load: bool) {
+        assert!(lc_addr >= LOCKED_CACHE_BASE);
+        assert!(lc_addr + blocks * CACHE_BLOCK_SIZE <= LOCKED_CACHE_BASE + LOCKED_CACHE_SIZE);
+        assert!(lc_addr % CACHE_BLOCK_SIZE == 0 && mem_addr % CACHE_BLOCK_SIZE == 0);
+
+        let mut lc_addr = lc_addr as u32;
+        let mut mem_addr = mem_addr as u32;
+        let mut remaining = blocks;
+        while remaining > 0 {
+            let count = remaining.min(DMA_MAX_BLOCKS);
+
+            // Wait for a free slot in the queue.
+            self.queue_wait(LOCKED_CACHE_QUEUE_DEPTH - 1);
+
+            // A length of 0 means 128 blocks, it is split across DMAU and DMAL.
+            let count_bits = (count % DMA_MAX_BLOCKS) as u32;
+            let upper = (mem_addr & 0x1fff_ffe0) | (count_bits >> 2);
+
+            // DMAL[LD] selects the direction, DMAL[T] triggers the transfer.
+            let lower = (lc_addr & 0xffff_ffe0) | ((load as u32) << 4) | ((count_bits & 0x3) << 2);
+            let lower = lower | 0x2;
+
+            // DMAU must be written before DMAL, which triggers the transfer.
+            spr::write(Spr::Dmau, upper);
+            spr::write(Spr::Dmal, lower);
+
+            lc_addr += (count * CACHE_BLOCK_SIZE) as u32;
+            mem_addr += (count * CACHE_BLOCK_SIZE) as u32;
+            remaining -= count;
+        }
+    }
+}
 
-    // Restore the CPU ISR
-    processor::cpu_isr_restore(level);
+impl Drop for LockedCache {
+    fn drop(&mut self) {
+        self.queue_wait(0);
+        unsafe { LCDisable() };
+        LOCKED_CACHE_TAKEN.store(false, Ordering::Release);
+    }
 }
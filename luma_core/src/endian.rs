@@ -0,0 +1,229 @@
+//! ``endian`` module of ``luma_core``.
+//!
+//! Contains the little-endian field types and memory accessors.
+//!
+//! Broadway is big-endian, but USB descriptors, FAT structures and Bluetooth packets are
+//! little-endian.  ``Le16``, ``Le32`` and ``Le64`` store a value in that order and are aligned on
+//! a single byte, so a ``#[repr(C)]`` struct of them maps such data without copying it, while
+//! ``read_le16`` and friends access little-endian memory through the byte-reversed loads and
+//! stores.
+
+use core::fmt;
+
+/// Define a little-endian field type.
+macro_rules! le_type {
+    ($(#[$doc:meta])* $name:ident, $ty:ty, $size:expr) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+        #[repr(transparent)]
+        pub struct $name([u8; $size]);
+
+        impl $name {
+            /// Create a little-endian field from its value.
+            #[inline(always)]
+            pub const fn new(value: $ty) -> $name {
+                $name(value.to_le_bytes())
+            }
+
+            /// Create a little-endian field from its bytes, as stored in memory.
+            #[inline(always)]
+            pub const fn from_bytes(bytes: [u8; $size]) -> $name {
+                $name(bytes)
+            }
+
+            /// Get the bytes of this field, as stored in memory.
+            #[inline(always)]
+            pub const fn to_bytes(self) -> [u8; $size] {
+                self.0
+            }
+
+            /// Get the value of this field.
+            #[inline(always)]
+            pub const fn get(self) -> $ty {
+                <$ty>::from_le_bytes(self.0)
+            }
+
+            /// Set the value of this field.
+            #[inline(always)]
+            pub fn set(&mut self, value: $ty) {
+                self.0 = value.to_le_bytes();
+            }
+        }
+
+        impl From<$ty> for $name {
+            fn from(value: $ty) -> $name {
+                $name::new(value)
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(field: $name) -> $ty {
+                field.get()
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.get(), f)
+            }
+        }
+    };
+}
+
+le_type! {
+    /// A 16-bit little-endian field.
+    Le16, u16, 2
+}
+
+le_type! {
+    /// A 32-bit little-endian field.
+    Le32, u32, 4
+}
+
+le_type! {
+    /// A 64-bit little-endian field.
+    Le64, u64, 8
+}
+
+/// Read a 16-bit little-endian value, with a volatile ``lhbrx``.
+///
+/// # Safety
+/// ``src`` **MUST** be valid for reads and aligned on a 2 byte boundary.
+#[inline(always)]
+pub unsafe fn read_le16(src: *const u16) -> u16 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        asm!("lhbrx {0},0,{1}",
+            lateout(reg) register,
+            in(reg) src,
+            options(nostack));
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    u16::from_le_bytes(core::ptr::read_volatile(src as *const [u8; 2]))
+}
+
+/// Read a 32-bit little-endian value, with a volatile ``lwbrx``.
+///
+/// # Safety
+/// ``src`` **MUST** be valid for reads and aligned on a 4 byte boundary.
+#[inline(always)]
+pub unsafe fn read_le32(src: *const u32) -> u32 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        asm!("lwbrx {0},0,{1}",
+            lateout(reg) register,
+            in(reg) src,
+            options(nostack));
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    u32::from_le_bytes(core::ptr::read_volatile(src as *const [u8; 4]))
+}
+
+/// Write a 16-bit little-endian value, with a volatile ``sthbrx``.
+///
+/// # Safety
+/// ``dst`` **MUST** be valid for writes and aligned on a 2 byte boundary.
+#[inline(always)]
+pub unsafe fn write_le16(dst: *mut u16, value: u16) {
+    #[cfg(target_arch = "powerpc")]
+    asm!("sthbrx {0},0,{1}",
+        in(reg) value, in(reg) dst,
+        options(nostack));
+
+    #[cfg(not(target_arch = "powerpc"))]
+    core::ptr::write_volatile(dst as *mut [u8; 2], value.to_le_bytes());
+}
+
+/// Write a 32-bit little-endian value, with a volatile ``stwbrx``.
+///
+/// # Safety
+/// ``dst`` **MUST** be valid for writes and aligned on a 4 byte boundary.
+#[inline(always)]
+pub unsafe fn write_le32(dst: *mut u32, value: u32) {
+    #[cfg(target_arch = "powerpc")]
+    asm!("stwbrx {0},0,{1}",
+        in(reg) value, in(reg) dst,
+        options(nostack));
+
+    #[cfg(not(target_arch = "powerpc"))]
+    core::ptr::write_volatile(dst as *mut [u8; 4], value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{align_of, size_of};
+
+    #[test]
+    fn le16_round_trip() {
+        let mut field = Le16::new(0x1234);
+        assert_eq!(field.get(), 0x1234);
+        assert_eq!(field.to_bytes(), [0x34, 0x12]);
+        assert_eq!(Le16::from_bytes([0x34, 0x12]), field);
+
+        field.set(0xbeef);
+        assert_eq!(field.to_bytes(), [0xef, 0xbe]);
+        assert_eq!(u16::from(field), 0xbeef);
+    }
+
+    #[test]
+    fn le32_round_trip() {
+        let field = Le32::new(0x1234_5678);
+        assert_eq!(field.get(), 0x1234_5678);
+        assert_eq!(field.to_bytes(), [0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(Le32::from(0x1234_5678), field);
+    }
+
+    #[test]
+    fn le64_round_trip() {
+        let field = Le64::new(0x0123_4567_89ab_cdef);
+        assert_eq!(field.get(), 0x0123_4567_89ab_cdef);
+        assert_eq!(
+            field.to_bytes(),
+            [0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01]
+        );
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(size_of::<Le16>(), 2);
+        assert_eq!(size_of::<Le32>(), 4);
+        assert_eq!(size_of::<Le64>(), 8);
+        assert_eq!(align_of::<Le16>(), 1);
+        assert_eq!(align_of::<Le32>(), 1);
+        assert_eq!(align_of::<Le64>(), 1);
+    }
+
+    #[test]
+    fn accessors() {
+        let mut word = 0u32;
+        unsafe {
+            write_le32(&mut word, 0x1234_5678);
+            assert_eq!(word.to_ne_bytes(), [0x78, 0x56, 0x34, 0x12]);
+            assert_eq!(read_le32(&word), 0x1234_5678);
+        }
+
+        let mut half = 0u16;
+        unsafe {
+            write_le16(&mut half, 0xbeef);
+            assert_eq!(half.to_ne_bytes(), [0xef, 0xbe]);
+            assert_eq!(read_le16(&half), 0xbeef);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! ``event`` module of ``luma_core``.
+//!
+//! Contains ``Event``, which counts the occurrences of something signalled by an interrupt
+//! handler, and wakes up the futures awaiting the next one.
+
+use crate::processor;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+struct State {
+    count: u64,
+    wakers: Vec<Waker>,
+}
+
+/// A source of occurrences, usually signalled from an interrupt handler.
+pub struct Event(UnsafeCell<State>);
+
+unsafe impl Sync for Event {}
+
+impl Default for Event {
+    fn default() -> Event {
+        Event::new()
+    }
+}
+
+impl Event {
+    /// Create an event which never occurred.
+    pub const fn new() -> Event {
+        Event(UnsafeCell::new(State {
+            count: 0,
+            wakers: Vec::new(),
+        }))
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        processor::critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+
+    /// Record an occurrence and wake up every future awaiting it.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn signal(&self) {
+        let wakers = self.with(|state| {
+            state.count += 1;
+            mem::take(&mut state.wakers)
+        });
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Get the number of occurrences so far.
+    pub fn count(&self) -> u64 {
+        self.with(|state| state.count)
+    }
+
+    /// Get a future resolving at the next occurrence, to the number of occurrences so far.
+    pub fn next(&self) -> Next<'_> {
+        Next {
+            event: self,
+            target: self.count() + 1,
+        }
+    }
+}
+
+/// Future returned by ``Event::next``.
+pub struct Next<'a> {
+    event: &'a Event,
+    target: u64,
+}
+
+impl Future for Next<'_> {
+    type Output = u64;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+        let target = self.target;
+        self.event.with(|state| {
+            if state.count >= target {
+                return Poll::Ready(state.count);
+            }
+            if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+}
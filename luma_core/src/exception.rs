@@ -0,0 +1,332 @@
+//! ``exception`` module of ``luma_core``.
+//!
+//! Contains the installation of the exception vectors and their dispatch to Rust.
+//!
+//! Every installed vector saves the full register context of the interrupted code on its stack,
+//! then calls the handler registered for that exception with translation enabled and interrupts
+//! disabled.  Exceptions without a handler are reported by panicking.
+//!
+//! Once the handler returns, an optional scheduler may pick another saved context to resume
+//! instead of the interrupted one, which is how the runtime switches threads.
+
+use crate::cache::{DCFlushRangeNS, ICInvalidateRange};
+use crate::{alarm, pi};
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(target_arch = "powerpc")]
+global_asm!(include_str!("../asm/exception.S"));
+
+// Load the vector stubs from global assembly.
+extern "C" {
+    static __exception_stub: u32;
+    static __exception_stub_vector: u32;
+    static __exception_stub_end: u32;
+    static __syscall_stub: u32;
+    static __syscall_stub_end: u32;
+    fn __exception_reschedule();
+}
+
+/// The exceptions Broadway can take, and the offset of their vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Exception {
+    /// System reset.
+    SystemReset,
+    /// Machine check, usually a bus error.
+    MachineCheck,
+    /// Data storage interrupt, a data access which couldn't be translated.
+    Dsi,
+    /// Instruction storage interrupt, an instruction fetch which couldn't be translated.
+    Isi,
+    /// External interrupt, raised by the processor interface.
+    External,
+    /// Misaligned access.
+    Alignment,
+    /// Program exception: illegal instruction, privileged instruction or trap.
+    Program,
+    /// Floating-point instruction executed while MSR[FP] is cleared.
+    FloatingPointUnavailable,
+    /// Decrementer expiry.
+    Decrementer,
+    /// Single-step or branch trace.
+    Trace,
+    /// Performance monitor counter overflow.
+    PerformanceMonitor,
+    /// Instruction address breakpoint (IABR).
+    InstructionBreakpoint,
+    /// System management interrupt.
+    SystemManagement,
+    /// Thermal management interrupt.
+    Thermal,
+}
+
+impl Exception {
+    /// Every exception dispatched to Rust.
+    pub const ALL: [Exception; 14] = [
+        Exception::SystemReset,
+        Exception::MachineCheck,
+        Exception::Dsi,
+        Exception::Isi,
+        Exception::External,
+        Exception::Alignment,
+        Exception::Program,
+        Exception::FloatingPointUnavailable,
+        Exception::Decrementer,
+        Exception::Trace,
+        Exception::PerformanceMonitor,
+        Exception::InstructionBreakpoint,
+        Exception::SystemManagement,
+        Exception::Thermal,
+    ];
+
+    /// Get the offset of this exception's vector.
+    pub fn vector(self) -> u32 {
+        match self {
+            Exception::SystemReset => 0x0100,
+            Exception::MachineCheck => 0x0200,
+            Exception::Dsi => 0x0300,
+            Exception::Isi => 0x0400,
+            Exception::External => 0x0500,
+            Exception::Alignment => 0x0600,
+            Exception::Program => 0x0700,
+            Exception::FloatingPointUnavailable => 0x0800,
+            Exception::Decrementer => 0x0900,
+            Exception::Trace => 0x0d00,
+            Exception::PerformanceMonitor => 0x0f00,
+            Exception::InstructionBreakpoint => 0x1300,
+            Exception::SystemManagement => 0x1400,
+            Exception::Thermal => 0x1700,
+        }
+    }
+
+    /// Get the exception for the given vector offset.
+    pub fn from_vector(vector: u32) -> Option<Exception> {
+        Exception::ALL
+            .iter()
+            .copied()
+            .find(|exception| exception.vector() == vector)
+    }
+
+    fn index(self) -> usize {
+        Exception::ALL
+            .iter()
+            .position(|&exception| exception == self)
+            .unwrap()
+    }
+}
+
+/// Offset of the system call vector, used for context synchronization rather than dispatched.
+const SYSTEM_CALL: u32 = 0x0c00;
+
+/// The register context saved when an exception is taken, restored when it returns.
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct Context {
+    /// General purpose registers.
+    pub gpr: [u32; 32],
+    /// Condition register.
+    pub cr: u32,
+    /// Link register.
+    pub lr: u32,
+    /// Count register.
+    pub ctr: u32,
+    /// Fixed-point exception register.
+    pub xer: u32,
+    /// Address the exception returns to.
+    pub srr0: u32,
+    /// MSR of the interrupted code.
+    pub srr1: u32,
+    /// Data address register.
+    pub dar: u32,
+    /// Data storage interrupt status register.
+    pub dsisr: u32,
+    _fpscr_high: u32,
+    /// Floating-point status and control register.
+    pub fpscr: u32,
+    /// Floating-point registers, which also hold the first half of paired singles.
+    pub fpr: [f64; 32],
+    /// Both halves of the paired singles, as stored through GQR0.
+    pub ps: [[f32; 2]; 32],
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in 0..8 {
+            for column in 0..4 {
+                let n = row + column * 8;
+                write!(f, "r{:<2} {:08x}  ", n, self.gpr[n])?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(
+            f,
+            "cr  {:08x}  lr  {:08x}  ctr {:08x}  xer {:08x}",
+            self.cr, self.lr, self.ctr, self.xer
+        )?;
+        write!(
+            f,
+            "srr0 {:08x} srr1 {:08x} dar {:08x}  dsisr {:08x}",
+            self.srr0, self.srr1, self.dar, self.dsisr
+        )
+    }
+}
+
+/// A Rust exception handler, called with the exception taken and the interrupted context.
+///
+/// Changes to the context are applied when the exception returns, for instance a handler
+/// resuming after a trap has to advance ``srr0`` past the trapping instruction.
+pub type Handler = fn(Exception, &mut Context);
+
+/// The registered handlers, only ever accessed with interrupts disabled.
+struct HandlerTable(UnsafeCell<[Option<Handler>; Exception::ALL.len()]>);
+
+unsafe impl Sync for HandlerTable {}
+
+impl HandlerTable {
+    fn with<R>(&self, f: impl FnOnce(&mut [Option<Handler>; Exception::ALL.len()]) -> R) -> R {
+        crate::processor::critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+}
+
+static HANDLERS: HandlerTable = HandlerTable(UnsafeCell::new([None; Exception::ALL.len()]));
+
+/// A scheduler, called with the context of the interrupted code once the exception has been
+/// handled, returning the context to resume.
+pub type Scheduler = fn(&mut Context) -> *mut Context;
+
+/// The installed scheduler, only ever accessed with interrupts disabled.
+struct SchedulerCell(UnsafeCell<Option<Scheduler>>);
+
+unsafe impl Sync for SchedulerCell {}
+
+static SCHEDULER: SchedulerCell = SchedulerCell(UnsafeCell::new(None));
+
+/// Number of exception handlers currently running.
+static DEPTH: AtomicU32 = AtomicU32::new(0);
+
+/// DABR value armed on the way out of every exception, the vector stubs clearing it so that the
+/// exception frame can't hit the breakpoint.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static __exception_dabr: AtomicU32 = AtomicU32::new(0);
+
+/// Register a handler for the given exception, returning the previously registered one.
+pub fn set_handler(exception: Exception, handler: Handler) -> Option<Handler> {
+    HANDLERS.with(|handlers| handlers[exception.index()].replace(handler))
+}
+
+/// Unregister the handler of the given exception, reverting to luma's own handling.
+pub fn clear_handler(exception: Exception) -> Option<Handler> {
+    HANDLERS.with(|handlers| handlers[exception.index()].take())
+}
+
+/// Install the scheduler called on the way out of every exception.
+///
+/// # Safety
+/// The returned context **MUST** be either the one passed in, or one which was previously passed
+/// to the scheduler and never resumed since.  It gets resumed with ``rfi``, so its ``srr1`` has
+/// to be a valid MSR.
+pub unsafe fn set_scheduler(scheduler: Scheduler) {
+    crate::processor::critical_section(|| *SCHEDULER.0.get() = Some(scheduler));
+}
+
+/// Whether the caller is running from an exception handler.
+pub fn in_exception() -> bool {
+    DEPTH.load(Ordering::Relaxed) != 0
+}
+
+/// Save the current context as if an exception had been taken, and resume the context the
+/// scheduler picks.  Returns once the scheduler resumes the saved context.
+pub fn reschedule() {
+    unsafe { __exception_reschedule() };
+}
+
+/// Set the data address breakpoint, a DSI being taken on any access it matches outside of the
+/// exception handlers, ``0`` disarming it.
+///
+/// **NOTE**: From an exception handler, the breakpoint only applies once the exception returns.
+pub fn set_data_breakpoint(dabr: u32) {
+    crate::processor::critical_section(|| {
+        __exception_dabr.store(dabr, Ordering::Relaxed);
+        if !in_exception() {
+            crate::spr::write(crate::spr::Spr::Dabr, dabr);
+        }
+    });
+}
+
+/// Report an exception nothing handles, and never return.
+pub fn default_handler(exception: Exception, context: &mut Context) {
+    panic!(
+        "Unhandled {:?} exception at {:08x}\n{}",
+        exception, context.srr0, context
+    );
+}
+
+/// The handler luma uses when none is registered.
+fn builtin_handler(exception: Exception) -> Handler {
+    match exception {
+        Exception::External => pi::external_handler,
+        Exception::Decrementer => |_, _| alarm::decrementer_handler(),
+        _ => default_handler,
+    }
+}
+
+/// Copy a stub to the given exception vector.
+unsafe fn install_stub(vector: u32, stub: *const u32, end: *const u32) -> *mut u32 {
+    let length = end as usize - stub as usize;
+    let dst = (0x8000_0000 | vector) as *mut u32;
+    core::ptr::copy_nonoverlapping(stub, dst, length / 4);
+    dst
+}
+
+/// Make sure the instruction fetch sees a freshly written vector.
+unsafe fn sync_vector(dst: *mut u32) {
+    DCFlushRangeNS(dst, 0x100);
+    ICInvalidateRange(dst, 0x100);
+}
+
+/// Install all of the exception vectors.
+pub fn install() {
+    unsafe {
+        let stub = &__exception_stub as *const u32;
+        let patch = &__exception_stub_vector as *const u32;
+        let end = &__exception_stub_end as *const u32;
+        for exception in Exception::ALL.iter() {
+            let dst = install_stub(exception.vector(), stub, end);
+
+            // ``li r4,0`` becomes ``li r4,vector``.
+            let index = (patch as usize - stub as usize) / 4;
+            *dst.add(index) |= exception.vector();
+            sync_vector(dst);
+        }
+
+        let stub = &__syscall_stub as *const u32;
+        let end = &__syscall_stub_end as *const u32;
+        let dst = install_stub(SYSTEM_CALL, stub, end);
+        sync_vector(dst);
+    }
+}
+
+/// Called by the vector stubs with the offset of the vector taken, or by ``reschedule`` with no
+/// vector.  Returns the context to resume.
+#[no_mangle]
+extern "C" fn __exception_dispatch(vector: u32, context: &mut Context) -> *mut Context {
+    if vector != 0 {
+        let exception = match Exception::from_vector(vector) {
+            Some(exception) => exception,
+            None => panic!("Exception from unknown vector {:04x}", vector),
+        };
+        let handler = HANDLERS
+            .with(|handlers| handlers[exception.index()])
+            .unwrap_or_else(|| builtin_handler(exception));
+        DEPTH.fetch_add(1, Ordering::Relaxed);
+        handler(exception, context);
+        DEPTH.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    match unsafe { *SCHEDULER.0.get() } {
+        Some(scheduler) => scheduler(context),
+        None => context,
+    }
+}
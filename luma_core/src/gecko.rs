@@ -0,0 +1,80 @@
+//! ``gecko`` module of ``luma_core``.
+//!
+//! Contains functions for the USB Gecko, a serial adapter plugged into memory card slot B.
+//!
+//! The adapter is driven through channel 1 of the external interface, one byte per 16-bit
+//! transfer.  Dolphin emulates it too, forwarding the output to a TCP socket.
+
+use crate::io::{read32, write32};
+use crate::processor;
+use core::fmt;
+
+/// Channel 1 of the external interface, slot B.
+const BASE: u32 = 0xcc00_6814;
+
+/// Channel parameter register.
+const CSR: u32 = BASE;
+
+/// Channel control register.
+const CR: u32 = BASE + 0x0c;
+
+/// Channel immediate data register.
+const DATA: u32 = BASE + 0x10;
+
+/// CSR bits kept when selecting a device, the interrupt masks.
+const CSR_KEEP: u32 = 0x405;
+
+/// CSR value selecting device 0 at 32 MHz.
+const CSR_SELECT: u32 = 0xd0;
+
+/// CR value starting a 2 byte read and write immediate transfer.
+const CR_TRANSFER: u32 = 0x19;
+
+/// Number of attempts at sending a byte before giving up on it.
+const SEND_ATTEMPTS: usize = 1000;
+
+/// Exchange a command with the adapter, returning its response.
+fn transfer(command: u32) -> u32 {
+    processor::critical_section(|| {
+        let csr = read32(CSR) & CSR_KEEP;
+        write32(CSR, csr | CSR_SELECT);
+        write32(DATA, command);
+        write32(CR, CR_TRANSFER);
+        while read32(CR) & 1 != 0 {}
+        let response = read32(DATA);
+        write32(CSR, csr);
+        response
+    })
+}
+
+/// Whether a USB Gecko answers in slot B.
+pub fn is_present() -> bool {
+    transfer(0x9000_0000) == 0x0470_0000
+}
+
+/// Try to send one byte, ``false`` if the adapter has no room for it yet.
+fn send_byte(byte: u8) -> bool {
+    transfer(0xb000_0000 | ((byte as u32) << 20)) & 0x0400_0000 != 0
+}
+
+/// Send bytes to the adapter, dropping those it keeps refusing, for instance because nothing
+/// reads the other end.
+pub fn write(bytes: &[u8]) {
+    for &byte in bytes {
+        for _ in 0..SEND_ATTEMPTS {
+            if send_byte(byte) {
+                break;
+            }
+        }
+    }
+}
+
+/// A ``fmt::Write`` implementation sending the text to the USB Gecko.
+pub struct Gecko;
+
+impl fmt::Write for Gecko {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(s.as_bytes());
+        Ok(())
+    }
+}
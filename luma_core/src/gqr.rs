@@ -0,0 +1,449 @@
+//! ``gqr`` module of ``luma_core``.
+//!
+//! Contains the graphics quantization registers, and the quantized paired-single loads and
+//! stores going through them.
+//!
+//! Each of the eight GQRs tells ``psq_l`` how to dequantize the pair of values it loads, and
+//! ``psq_st`` how to quantize the pair it stores: their integer type, and the power of two they
+//! get scaled by.  This converts packed vertex or audio data to floats in a single instruction.
+//!
+//! **NOTE**: GQR0 is kept unquantized and unscaled, as the exception vectors and the ``paired``
+//! module rely on it to move paired singles around.
+//!
+//! Other targets keep the GQRs in memory and quantize in software, so that code using this
+//! module also runs on the host.
+
+use crate::paired::f32x2;
+#[cfg(target_arch = "powerpc")]
+use crate::{mfspr, mtspr};
+#[cfg(not(target_arch = "powerpc"))]
+use core::ptr;
+#[cfg(not(target_arch = "powerpc"))]
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The type of the quantized values in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuantType {
+    /// Unquantized single-precision floats, never scaled.
+    F32,
+    /// Unsigned 8-bit integers.
+    U8,
+    /// Unsigned 16-bit integers.
+    U16,
+    /// Signed 8-bit integers.
+    S8,
+    /// Signed 16-bit integers.
+    S16,
+}
+
+impl QuantType {
+    /// Get the encoding of this type in a GQR.
+    pub const fn bits(self) -> u32 {
+        match self {
+            QuantType::F32 => 0,
+            QuantType::U8 => 4,
+            QuantType::U16 => 5,
+            QuantType::S8 => 6,
+            QuantType::S16 => 7,
+        }
+    }
+
+    /// Get the type for its encoding in a GQR, the reserved encodings have none.
+    pub fn from_bits(bits: u32) -> Option<QuantType> {
+        match bits {
+            0 => Some(QuantType::F32),
+            4 => Some(QuantType::U8),
+            5 => Some(QuantType::U16),
+            6 => Some(QuantType::S8),
+            7 => Some(QuantType::S16),
+            _ => None,
+        }
+    }
+
+    /// Get the size of one quantized value, in bytes.
+    pub const fn size(self) -> usize {
+        match self {
+            QuantType::F32 => 4,
+            QuantType::U8 | QuantType::S8 => 1,
+            QuantType::U16 | QuantType::S16 => 2,
+        }
+    }
+}
+
+/// The value of a graphics quantization register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Gqr(u32);
+
+impl Gqr {
+    /// Create a GQR value from its load and store types and scales.
+    ///
+    /// Loaded values are multiplied by 2^-``load_scale``, stored ones by 2^``store_scale``, both
+    /// scales range from -32 to 31.
+    pub const fn new(
+        load_type: QuantType,
+        load_scale: i8,
+        store_type: QuantType,
+        store_scale: i8,
+    ) -> Gqr {
+        Gqr(((load_scale as u32 & 0x3f) << 24)
+            | (load_type.bits() << 16)
+            | ((store_scale as u32 & 0x3f) << 8)
+            | store_type.bits())
+    }
+
+    /// Create a GQR value using the same type and scale for loads and stores.
+    pub const fn symmetric(quant_type: QuantType, scale: i8) -> Gqr {
+        Gqr::new(quant_type, scale, quant_type, scale)
+    }
+
+    /// Create a GQR value from its raw bits.
+    pub const fn from_bits(bits: u32) -> Gqr {
+        Gqr(bits)
+    }
+
+    /// Get the raw bits of this GQR value.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Get the type of the values ``psq_l`` loads.
+    pub fn load_type(self) -> Option<QuantType> {
+        QuantType::from_bits((self.0 >> 16) & 7)
+    }
+
+    /// Get the scale of the values ``psq_l`` loads.
+    pub fn load_scale(self) -> i8 {
+        // Sign-extend the 6-bit field.
+        ((self.0 >> 22) as i8) >> 2
+    }
+
+    /// Get the type of the values ``psq_st`` stores.
+    pub fn store_type(self) -> Option<QuantType> {
+        QuantType::from_bits(self.0 & 7)
+    }
+
+    /// Get the scale of the values ``psq_st`` stores.
+    pub fn store_scale(self) -> i8 {
+        // Sign-extend the 6-bit field.
+        ((self.0 >> 6) as i8) >> 2
+    }
+}
+
+/// Run ``$body!`` with the GQR index ``$index`` and its SPR number as literals.
+macro_rules! with_index {
+    ($index:expr, $body:ident) => {
+        match $index {
+            0 => $body!(0, 912),
+            1 => $body!(1, 913),
+            2 => $body!(2, 914),
+            3 => $body!(3, 915),
+            4 => $body!(4, 916),
+            5 => $body!(5, 917),
+            6 => $body!(6, 918),
+            7 => $body!(7, 919),
+            _ => panic!("There are only eight GQRs"),
+        }
+    };
+}
+
+/// The GQRs of other targets.
+#[cfg(not(target_arch = "powerpc"))]
+static GQRS: [AtomicU32; 8] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+
+/// Read the given GQR.
+#[inline(always)]
+pub fn read(index: usize) -> Gqr {
+    #[cfg(target_arch = "powerpc")]
+    macro_rules! mfgqr {
+        ($n:literal, $spr:tt) => {
+            Gqr(mfspr!($spr))
+        };
+    }
+    #[cfg(not(target_arch = "powerpc"))]
+    macro_rules! mfgqr {
+        ($n:literal, $spr:tt) => {
+            Gqr(GQRS[$n].load(Ordering::Relaxed))
+        };
+    }
+    with_index!(index, mfgqr)
+}
+
+/// Write the given GQR, from 1 to 7.
+///
+/// **NOTE**: This function panics if ``index`` is 0, see the module documentation.
+#[inline(always)]
+pub fn write(index: usize, gqr: Gqr) {
+    assert!(index != 0, "GQR0 has to stay unquantized");
+    #[cfg(target_arch = "powerpc")]
+    macro_rules! mtgqr {
+        ($n:literal, $spr:tt) => {
+            mtspr!(gqr.0, $spr)
+        };
+    }
+    #[cfg(not(target_arch = "powerpc"))]
+    macro_rules! mtgqr {
+        ($n:literal, $spr:tt) => {
+            GQRS[$n].store(gqr.0, Ordering::Relaxed)
+        };
+    }
+    with_index!(index, mtgqr)
+}
+
+/// Read every GQR, for instance when switching threads.
+pub fn save() -> [Gqr; 8] {
+    [
+        read(0),
+        read(1),
+        read(2),
+        read(3),
+        read(4),
+        read(5),
+        read(6),
+        read(7),
+    ]
+}
+
+/// Write back every GQR, as previously returned by ``save``.
+pub fn restore(gqrs: &[Gqr; 8]) {
+    #[cfg(target_arch = "powerpc")]
+    mtspr!(gqrs[0].0, 912);
+    #[cfg(not(target_arch = "powerpc"))]
+    GQRS[0].store(gqrs[0].0, Ordering::Relaxed);
+    for (index, &gqr) in gqrs.iter().enumerate().skip(1) {
+        write(index, gqr);
+    }
+}
+
+/// (`psq_l`) Paired-Single Quantized Load, of a pair dequantized through the given GQR.
+///
+/// # Safety
+/// ``src`` **MUST** be valid for reading a pair of the load type of that GQR.
+#[inline(always)]
+pub unsafe fn psq_l(src: *const u8, index: usize) -> f32x2 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a paired-single output variable.
+        let mut pair = f32x2::default();
+
+        // Run the assembly instruction, then store the pair through GQR0.
+        macro_rules! psq_l {
+            ($n:literal, $spr:tt) => {
+                asm!(concat!(".long (56<<26)|({f}<<21)|({src}<<16)|(", $n, "<<12)"),
+                     ".long (60<<26)|({f}<<21)|({dst}<<16)",
+                    src = in(reg_nonzero) src,
+                    dst = in(reg_nonzero) &mut pair,
+                    f = out(freg) _,
+                    options(nostack))
+            };
+        }
+        with_index!(index, psq_l);
+
+        // Return the register value.
+        pair
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let gqr = read(index);
+        let quant_type = gqr.load_type().unwrap_or(QuantType::F32);
+        let scale = gqr.load_scale();
+        f32x2::new(
+            dequantize(src, quant_type, scale),
+            dequantize(src.add(quant_type.size()), quant_type, scale),
+        )
+    }
+}
+
+/// (`psq_st`) Paired-Single Quantized Store, of a pair quantized through the given GQR.
+///
+/// # Safety
+/// ``dst`` **MUST** be valid for writing a pair of the store type of that GQR.
+#[inline(always)]
+pub unsafe fn psq_st(dst: *mut u8, pair: f32x2, index: usize) {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Load the pair through GQR0, then run the assembly instruction.
+        macro_rules! psq_st {
+            ($n:literal, $spr:tt) => {
+                asm!(".long (56<<26)|({f}<<21)|({src}<<16)",
+                     concat!(".long (60<<26)|({f}<<21)|({dst}<<16)|(", $n, "<<12)"),
+                    src = in(reg_nonzero) &pair,
+                    dst = in(reg_nonzero) dst,
+                    f = out(freg) _,
+                    options(nostack))
+            };
+        }
+        with_index!(index, psq_st);
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let gqr = read(index);
+        let quant_type = gqr.store_type().unwrap_or(QuantType::F32);
+        let scale = gqr.store_scale();
+        quantize(dst, pair.ps0(), quant_type, scale);
+        quantize(dst.add(quant_type.size()), pair.ps1(), quant_type, scale);
+    }
+}
+
+/// Get 2^``exponent``, for the scales of other targets.
+#[cfg(not(target_arch = "powerpc"))]
+fn exp2(exponent: i8) -> f32 {
+    f32::from_bits(((127 + exponent as i32) as u32) << 23)
+}
+
+/// Load and dequantize one value, as ``psq_l`` does on other targets.
+///
+/// Floats are never scaled, and the reserved types load as floats.
+#[cfg(not(target_arch = "powerpc"))]
+unsafe fn dequantize(src: *const u8, quant_type: QuantType, scale: i8) -> f32 {
+    let value = match quant_type {
+        QuantType::F32 => return ptr::read_unaligned(src as *const f32),
+        QuantType::U8 => ptr::read(src) as f32,
+        QuantType::U16 => ptr::read_unaligned(src as *const u16) as f32,
+        QuantType::S8 => ptr::read(src as *const i8) as f32,
+        QuantType::S16 => ptr::read_unaligned(src as *const i16) as f32,
+    };
+    value * exp2(-scale)
+}
+
+/// Quantize and store one value, as ``psq_st`` does on other targets.
+///
+/// Integers are rounded toward zero and saturated, floats are never scaled.
+#[cfg(not(target_arch = "powerpc"))]
+unsafe fn quantize(dst: *mut u8, value: f32, quant_type: QuantType, scale: i8) {
+    let scaled = value * exp2(scale);
+    match quant_type {
+        QuantType::F32 => ptr::write_unaligned(dst as *mut f32, value),
+        QuantType::U8 => ptr::write(dst, scaled as u8),
+        QuantType::U16 => ptr::write_unaligned(dst as *mut u16, scaled as u16),
+        QuantType::S8 => ptr::write(dst as *mut i8, scaled as i8),
+        QuantType::S16 => ptr::write_unaligned(dst as *mut i16, scaled as i16),
+    }
+}
+
+/// A type ``psq_l`` and ``psq_st`` can convert from and to.
+pub trait Quantized: Copy {
+    /// The GQR type of these values.
+    const TYPE: QuantType;
+}
+
+impl Quantized for f32 {
+    const TYPE: QuantType = QuantType::F32;
+}
+
+impl Quantized for u8 {
+    const TYPE: QuantType = QuantType::U8;
+}
+
+impl Quantized for u16 {
+    const TYPE: QuantType = QuantType::U16;
+}
+
+impl Quantized for i8 {
+    const TYPE: QuantType = QuantType::S8;
+}
+
+impl Quantized for i16 {
+    const TYPE: QuantType = QuantType::S16;
+}
+
+/// Load and dequantize a pair of values, through a GQR whose load type matches ``T``.
+///
+/// **NOTE**: In debug builds, this function panics if the load type of the GQR isn't ``T``.
+#[inline(always)]
+pub fn load<T: Quantized>(src: &[T; 2], index: usize) -> f32x2 {
+    debug_assert_eq!(read(index).load_type(), Some(T::TYPE));
+    unsafe { psq_l(src.as_ptr() as *const u8, index) }
+}
+
+/// Quantize and store a pair of values, through a GQR whose store type matches ``T``.
+///
+/// **NOTE**: In debug builds, this function panics if the store type of the GQR isn't ``T``.
+#[inline(always)]
+pub fn store<T: Quantized>(dst: &mut [T; 2], pair: f32x2, index: usize) {
+    debug_assert_eq!(read(index).store_type(), Some(T::TYPE));
+    unsafe { psq_st(dst.as_mut_ptr() as *mut u8, pair, index) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPES: [QuantType; 5] = [
+        QuantType::F32,
+        QuantType::U8,
+        QuantType::U16,
+        QuantType::S8,
+        QuantType::S16,
+    ];
+
+    #[test]
+    fn encode() {
+        let gqr = Gqr::new(QuantType::U16, 8, QuantType::S8, -4);
+        assert_eq!(gqr.bits(), 0x0805_3c06);
+        assert_eq!(Gqr::symmetric(QuantType::S16, -32).bits(), 0x2007_2007);
+        assert_eq!(Gqr::from_bits(0x0001_0003).load_type(), None);
+        assert_eq!(Gqr::from_bits(0x0001_0003).store_type(), None);
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        for &load_type in TYPES.iter() {
+            for &store_type in TYPES.iter() {
+                for scale in -32..=31 {
+                    let gqr = Gqr::new(load_type, scale, store_type, -1 - scale);
+                    assert_eq!(gqr.load_type(), Some(load_type));
+                    assert_eq!(gqr.load_scale(), scale);
+                    assert_eq!(gqr.store_type(), Some(store_type));
+                    assert_eq!(gqr.store_scale(), -1 - scale);
+                    assert_eq!(QuantType::from_bits(load_type.bits()), Some(load_type));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quantized_load_store() {
+        write(3, Gqr::symmetric(QuantType::S16, 8));
+        let mut s16 = [0i16; 2];
+        store(&mut s16, f32x2::new(-1.5, 2.25), 3);
+        assert_eq!(s16, [-384, 576]);
+        assert_eq!(load(&s16, 3), f32x2::new(-1.5, 2.25));
+
+        // Negative scales multiply on load and divide on store.
+        write(3, Gqr::symmetric(QuantType::U8, -2));
+        let mut u8s = [0u8; 2];
+        store(&mut u8s, f32x2::new(8.0, 1020.0), 3);
+        assert_eq!(u8s, [2, 255]);
+        assert_eq!(load(&u8s, 3), f32x2::new(8.0, 1020.0));
+
+        // Out of range values saturate, and fractions round toward zero.
+        write(3, Gqr::symmetric(QuantType::S8, 0));
+        let mut s8s = [0i8; 2];
+        store(&mut s8s, f32x2::new(-1000.0, -1.75), 3);
+        assert_eq!(s8s, [-128, -1]);
+
+        // Floats are never scaled.
+        write(3, Gqr::new(QuantType::F32, 5, QuantType::F32, 5));
+        let mut f32s = [0.0f32; 2];
+        store(&mut f32s, f32x2::new(0.5, -3.0), 3);
+        assert_eq!(f32s, [0.5, -3.0]);
+        assert_eq!(load(&f32s, 3), f32x2::new(0.5, -3.0));
+
+        let saved = save();
+        assert_eq!(saved[3], Gqr::symmetric(QuantType::F32, 5));
+        write(3, Gqr::default());
+        restore(&saved);
+        assert_eq!(read(3), saved[3]);
+    }
+}
@@ -0,0 +1,178 @@
+//! ``hollywood`` module of ``luma_core``.
+//!
+//! Contains the Hollywood interrupt controller of the Wii.
+//!
+//! IPC and the other Starlet-side interrupts are cascaded behind the ``Hollywood`` source of the
+//! processor interface.  Pending sources are dispatched to the handler registered for them, then
+//! acknowledged in the Hollywood flag register once the handler has acknowledged its device.
+
+use crate::io::{read32, write32};
+use crate::pi;
+use crate::processor;
+use core::cell::UnsafeCell;
+
+const BASE: u32 = 0xcd00_0000;
+
+/// Broadway IRQ flag register.
+const PPCIRQFLAG: u32 = BASE + 0x30;
+
+/// Broadway IRQ mask register.
+const PPCIRQMASK: u32 = BASE + 0x34;
+
+/// An interrupt source of the Hollywood interrupt controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interrupt {
+    /// Starlet timer.
+    Timer,
+    /// NAND interface.
+    Nand,
+    /// AES engine.
+    Aes,
+    /// SHA-1 engine.
+    Sha,
+    /// USB EHCI controller.
+    Ehci,
+    /// First USB OHCI controller.
+    Ohci0,
+    /// Second USB OHCI controller.
+    Ohci1,
+    /// SD host controller.
+    Sdhc,
+    /// Wireless SDIO controller.
+    Wifi,
+    /// GPIOs owned by Broadway.
+    GpioBroadway,
+    /// GPIOs owned by Starlet.
+    GpioStarlet,
+    /// IPC message towards Broadway.
+    Ipc,
+    /// IPC message towards Starlet.
+    IpcStarlet,
+}
+
+impl Interrupt {
+    /// Every interrupt source.
+    pub const ALL: [Interrupt; 13] = [
+        Interrupt::Timer,
+        Interrupt::Nand,
+        Interrupt::Aes,
+        Interrupt::Sha,
+        Interrupt::Ehci,
+        Interrupt::Ohci0,
+        Interrupt::Ohci1,
+        Interrupt::Sdhc,
+        Interrupt::Wifi,
+        Interrupt::GpioBroadway,
+        Interrupt::GpioStarlet,
+        Interrupt::Ipc,
+        Interrupt::IpcStarlet,
+    ];
+
+    fn index(self) -> usize {
+        Interrupt::ALL
+            .iter()
+            .position(|&interrupt| interrupt == self)
+            .unwrap()
+    }
+
+    /// Get the bit of this source in the flag and mask registers.
+    pub fn bit(self) -> u32 {
+        let shift = match self {
+            Interrupt::Timer => 0,
+            Interrupt::Nand => 1,
+            Interrupt::Aes => 2,
+            Interrupt::Sha => 3,
+            Interrupt::Ehci => 4,
+            Interrupt::Ohci0 => 5,
+            Interrupt::Ohci1 => 6,
+            Interrupt::Sdhc => 7,
+            Interrupt::Wifi => 8,
+            Interrupt::GpioBroadway => 10,
+            Interrupt::GpioStarlet => 11,
+            Interrupt::Ipc => 30,
+            Interrupt::IpcStarlet => 31,
+        };
+        1 << shift
+    }
+}
+
+/// A Rust interrupt handler, called with the pending source.
+pub type Handler = fn(Interrupt);
+
+/// The registered handlers, only ever accessed with interrupts disabled.
+struct HandlerTable(UnsafeCell<[Option<Handler>; Interrupt::ALL.len()]>);
+
+unsafe impl Sync for HandlerTable {}
+
+impl HandlerTable {
+    fn with<R>(&self, f: impl FnOnce(&mut [Option<Handler>; Interrupt::ALL.len()]) -> R) -> R {
+        processor::critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+}
+
+static HANDLERS: HandlerTable = HandlerTable(UnsafeCell::new([None; Interrupt::ALL.len()]));
+
+/// Mask and clear every source, then cascade the processor interface ``Hollywood`` source here.
+///
+/// Called by the runtime before interrupts get enabled.
+pub fn init() {
+    write32(PPCIRQMASK, 0);
+    write32(PPCIRQFLAG, 0xffff_ffff);
+    pi::register(pi::Interrupt::Hollywood, dispatch);
+}
+
+/// Register a handler for the given source and unmask it, returning the previous handler.
+pub fn register(interrupt: Interrupt, handler: Handler) -> Option<Handler> {
+    HANDLERS.with(|handlers| {
+        let previous = handlers[interrupt.index()].replace(handler);
+        unmask(interrupt);
+        previous
+    })
+}
+
+/// Mask the given source and unregister its handler.
+pub fn unregister(interrupt: Interrupt) -> Option<Handler> {
+    HANDLERS.with(|handlers| {
+        mask(interrupt);
+        handlers[interrupt.index()].take()
+    })
+}
+
+/// Stop the given source from interrupting Broadway.
+pub fn mask(interrupt: Interrupt) {
+    processor::critical_section(|| write32(PPCIRQMASK, read32(PPCIRQMASK) & !interrupt.bit()));
+}
+
+/// Allow the given source to interrupt Broadway.
+pub fn unmask(interrupt: Interrupt) {
+    processor::critical_section(|| write32(PPCIRQMASK, read32(PPCIRQMASK) | interrupt.bit()));
+}
+
+/// Whether the given source is currently allowed to interrupt Broadway.
+pub fn is_unmasked(interrupt: Interrupt) -> bool {
+    read32(PPCIRQMASK) & interrupt.bit() != 0
+}
+
+/// Whether the given source is currently flagged, masked or not.
+pub fn is_pending(interrupt: Interrupt) -> bool {
+    read32(PPCIRQFLAG) & interrupt.bit() != 0
+}
+
+/// Dispatch every pending and unmasked source to its handler.
+///
+/// Sources nothing handles get masked, so that they can't keep the CPU in the exception.
+fn dispatch(_interrupt: pi::Interrupt) {
+    let pending = read32(PPCIRQFLAG) & read32(PPCIRQMASK);
+    for &interrupt in Interrupt::ALL.iter() {
+        if pending & interrupt.bit() == 0 {
+            continue;
+        }
+
+        match HANDLERS.with(|handlers| handlers[interrupt.index()]) {
+            Some(handler) => handler(interrupt),
+            None => mask(interrupt),
+        }
+
+        write32(PPCIRQFLAG, interrupt.bit());
+    }
+}
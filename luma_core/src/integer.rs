@@ -1,21 +1,301 @@
 //! ``integer`` module of ``luma_core``.
 //!
 //! Contains functions for integer instructions.
+//!
+//! Every instruction has a pure Rust equivalent used on targets other than Broadway, so code
+//! built on them can run on the host.  The rotate and mask instructions encode their shift and
+//! mask bounds in the instruction itself, so they are macros taking literals, like ``mfspr``.
+//!
+//! **NOTE**: Bits are numbered the PowerPC way, bit 0 being the most significant one.
+
+/// Build the mask of the bits ``mb`` through ``me``, wrapping around when ``mb > me``, as the
+/// rotate and mask instructions do.
+#[inline(always)]
+pub const fn mask(mb: u32, me: u32) -> u32 {
+    let begin = u32::MAX >> (mb & 31);
+    let end = u32::MAX << (31 - (me & 31));
+    if mb <= me {
+        begin & end
+    } else {
+        begin | end
+    }
+}
+
+/// (`rlwinm`) PowerPC Integer Instruction
+///
+/// Rotate ``value`` left by ``sh`` bits, then AND it with the mask of bits ``mb`` through ``me``.
+#[macro_export]
+macro_rules! rlwinm {
+    ($value:expr, $sh:tt, $mb:tt, $me:tt) => {{
+        let value: u32 = $value;
+
+        #[cfg(target_arch = "powerpc")]
+        let register = unsafe {
+            let register: u32;
+            asm!(concat!("rlwinm {0},{1},", stringify!($sh), ",", stringify!($mb), ",",
+                         stringify!($me)),
+                lateout(reg) register, in(reg) value,
+                options(pure, nomem, nostack));
+            register
+        };
+
+        #[cfg(not(target_arch = "powerpc"))]
+        let register = value.rotate_left($sh) & $crate::integer::mask($mb, $me);
+
+        register
+    }};
+}
+
+/// (`rlwimi`) PowerPC Integer Instruction
+///
+/// Rotate ``value`` left by ``sh`` bits, then insert it into ``target`` under the mask of bits
+/// ``mb`` through ``me``.
+#[macro_export]
+macro_rules! rlwimi {
+    ($target:expr, $value:expr, $sh:tt, $mb:tt, $me:tt) => {{
+        let target: u32 = $target;
+        let value: u32 = $value;
+
+        #[cfg(target_arch = "powerpc")]
+        let register = unsafe {
+            let mut register = target;
+            asm!(concat!("rlwimi {0},{1},", stringify!($sh), ",", stringify!($mb), ",",
+                         stringify!($me)),
+                inout(reg) register, in(reg) value,
+                options(pure, nomem, nostack));
+            register
+        };
+
+        #[cfg(not(target_arch = "powerpc"))]
+        let register = {
+            let mask = $crate::integer::mask($mb, $me);
+            (value.rotate_left($sh) & mask) | (target & !mask)
+        };
+
+        register
+    }};
+}
+
+/// (`rlwnm`) PowerPC Integer Instruction
+///
+/// Rotate ``value`` left by the low 5 bits of ``shift``, then AND it with the mask of bits
+/// ``mb`` through ``me``.
+#[macro_export]
+macro_rules! rlwnm {
+    ($value:expr, $shift:expr, $mb:tt, $me:tt) => {{
+        let value: u32 = $value;
+        let shift: u32 = $shift;
+
+        #[cfg(target_arch = "powerpc")]
+        let register = unsafe {
+            let register: u32;
+            asm!(concat!("rlwnm {0},{1},{2},", stringify!($mb), ",", stringify!($me)),
+                lateout(reg) register, in(reg) value, in(reg) shift,
+                options(pure, nomem, nostack));
+            register
+        };
+
+        #[cfg(not(target_arch = "powerpc"))]
+        let register = value.rotate_left(shift & 31) & $crate::integer::mask($mb, $me);
+
+        register
+    }};
+}
+
+/// Define a function for a two operand integer instruction, and its pure Rust equivalent.
+macro_rules! binary {
+    ($(#[$doc:meta])* $name:ident($a:ident: $ty:ty, $b:ident: $ty2:ty) -> $ret:ty
+        = $fallback:expr) => {
+        $(#[$doc])*
+        #[inline(always)]
+        pub fn $name($a: $ty, $b: $ty2) -> $ret {
+            #[cfg(target_arch = "powerpc")]
+            {
+                // Define a register output variable.
+                let mut register;
+
+                // Run the assembly instruction.
+                unsafe {
+                    asm!(concat!(stringify!($name), " {0}, {1}, {2}"),
+                        lateout(reg) register,
+                        in(reg) $a, in(reg) $b,
+                        options(pure, nomem, nostack));
+                }
+
+                // Return the register value.
+                register
+            }
+
+            #[cfg(not(target_arch = "powerpc"))]
+            {
+                $fallback
+            }
+        }
+    };
+}
 
 /// (`cntlzw`) PowerPC Integer Instruction
 #[inline(always)]
 pub fn cntlzw(value: u32) -> u32 {
-    // Define a register output variable.
-    let mut register;
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        unsafe {
+            asm!("cntlzw {0}, {1}",
+                lateout(reg) register,
+                in(reg) value,
+                options(pure, nomem, nostack));
+        }
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        value.leading_zeros()
+    }
+}
+
+binary! {
+    /// (`mulhw`) PowerPC Integer Instruction
+    ///
+    /// Returns the upper 32 bits of the signed 64-bit product.
+    mulhw(a: i32, b: i32) -> i32 = ((a as i64 * b as i64) >> 32) as i32
+}
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("cntlzw {0}, {1}",
-            lateout(reg) register,
-            in(reg) value,
-            options(nostack));
+binary! {
+    /// (`mulhwu`) PowerPC Integer Instruction
+    ///
+    /// Returns the upper 32 bits of the unsigned 64-bit product.
+    mulhwu(a: u32, b: u32) -> u32 = ((a as u64 * b as u64) >> 32) as u32
+}
+
+binary! {
+    /// (`rotlw`) PowerPC Integer Instruction
+    ///
+    /// Rotates ``value`` left by the low 5 bits of ``shift``.
+    rotlw(value: u32, shift: u32) -> u32 = value.rotate_left(shift & 31)
+}
+
+binary! {
+    /// (`eqv`) PowerPC Integer Instruction
+    ///
+    /// Returns the complement of ``a ^ b``.
+    eqv(a: u32, b: u32) -> u32 = !(a ^ b)
+}
+
+binary! {
+    /// (`nand`) PowerPC Integer Instruction
+    ///
+    /// Returns the complement of ``a & b``.
+    nand(a: u32, b: u32) -> u32 = !(a & b)
+}
+
+binary! {
+    /// (`nor`) PowerPC Integer Instruction
+    ///
+    /// Returns the complement of ``a | b``.
+    nor(a: u32, b: u32) -> u32 = !(a | b)
+}
+
+binary! {
+    /// (`andc`) PowerPC Integer Instruction
+    ///
+    /// Returns ``a & !b``.
+    andc(a: u32, b: u32) -> u32 = a & !b
+}
+
+binary! {
+    /// (`orc`) PowerPC Integer Instruction
+    ///
+    /// Returns ``a | !b``.
+    orc(a: u32, b: u32) -> u32 = a | !b
+}
+
+/// Reverse the bytes of a 16-bit value.
+///
+/// Broadway has no register byte reverse instruction, this is the usual ``rlwinm`` and
+/// ``rlwimi`` sequence.  Use ``lhbrx`` and ``sthbrx`` when the value comes from memory.
+#[inline(always)]
+pub fn byte_reverse16(value: u16) -> u16 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        let value = value as u32;
+        let register = rlwinm!(value, 8, 16, 23);
+        rlwimi!(register, value, 24, 24, 31) as u16
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        value.swap_bytes()
     }
+}
 
-    // Return the register value.
-    register
+/// Reverse the bytes of a 32-bit value.
+///
+/// Broadway has no register byte reverse instruction, this is the usual ``rlwinm`` and
+/// ``rlwimi`` sequence.  Use ``lwbrx`` and ``stwbrx`` when the value comes from memory.
+#[inline(always)]
+pub fn byte_reverse32(value: u32) -> u32 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        let register = rlwinm!(value, 8, 0, 31);
+        let register = rlwimi!(register, value, 24, 0, 7);
+        rlwimi!(register, value, 24, 16, 23)
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        value.swap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks() {
+        assert_eq!(mask(0, 31), u32::MAX);
+        assert_eq!(mask(5, 5), 0x0400_0000);
+        assert_eq!(mask(16, 23), 0x0000_ff00);
+        assert_eq!(mask(28, 3), 0xf000_000f);
+        assert_eq!(mask(31, 0), 0x8000_0001);
+    }
+
+    #[test]
+    fn rotate_and_mask() {
+        assert_eq!(rlwinm!(0x1234_5678, 8, 24, 31), 0x12);
+        assert_eq!(rlwinm!(0x1234_5678, 0, 16, 31), 0x5678);
+        assert_eq!(rlwinm!(0x1234_5678, 4, 0, 27), 0x2345_6780);
+        assert_eq!(rlwinm!(0x1234_5678, 28, 4, 31), 0x0123_4567);
+
+        assert_eq!(rlwimi!(0xaaaa_aaaa, 0x1234_5678, 16, 0, 15), 0x5678_aaaa);
+        assert_eq!(rlwimi!(0xaaaa_aaaa, 0x1234_5678, 0, 28, 3), 0x1aaa_aaa8);
+
+        assert_eq!(rlwnm!(0x1234_5678, 36, 0, 31), 0x2345_6781);
+        assert_eq!(rlwnm!(0x8000_0001, 1, 31, 31), 1);
+        assert_eq!(rotlw(0x8000_0001, 33), 0x0000_0003);
+    }
+
+    #[test]
+    fn multiply_high() {
+        assert_eq!(mulhw(-2, 0x4000_0000), -1);
+        assert_eq!(mulhw(0x4000_0000, 0x4000_0000), 0x1000_0000);
+        assert_eq!(mulhw(i32::MIN, i32::MIN), 0x4000_0000);
+        assert_eq!(mulhwu(0xffff_ffff, 0xffff_ffff), 0xffff_fffe);
+        assert_eq!(mulhwu(0x8000_0000, 2), 1);
+    }
+
+    #[test]
+    fn byte_reverse() {
+        assert_eq!(byte_reverse16(0x1234), 0x3412);
+        assert_eq!(byte_reverse16(0xff00), 0x00ff);
+        assert_eq!(byte_reverse32(0x1234_5678), 0x7856_3412);
+        assert_eq!(byte_reverse32(0x8000_0001), 0x0100_0080);
+    }
 }
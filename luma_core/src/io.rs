@@ -1,101 +1,241 @@
 //! ``io`` module of ``luma_core``.
 //!
 //! Contains functions for basic I/O.
+//!
+//! Every access goes through the uncached mapping of the address.  Other targets have no
+//! hardware registers, so the functions only build there, and panic when called.
+
+/// Report an access to a hardware register on another target.
+#[cfg(not(target_arch = "powerpc"))]
+#[cold]
+fn no_mmio(address: u32) -> ! {
+    panic!("No hardware register at {:#010x} on this target", address)
+}
 
 /// Read a 32-bit value from an address.
 #[inline(always)]
 pub fn read32(address: u32) -> u32 {
-    // Define an output variable.
-    let mut register;
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define an output variable.
+        let mut register;
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("lwz {0},0({1}) ; sync",
-            lateout(reg) register, 
-            in(reg) (0xc000_0000 | address),
-            options(nostack));
+        // Run the assembly instruction.
+        unsafe {
+            asm!("lwz {0},0({1}) ; sync",
+                lateout(reg) register,
+                in(reg) (0xc000_0000 | address),
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
     }
 
-    // Return the register value.
-    register
+    #[cfg(not(target_arch = "powerpc"))]
+    no_mmio(address)
 }
 
 /// Write a 32-bit value to an address.
 #[inline(always)]
 pub fn write32(address: u32, value: u32) {
     // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
-        asm!("stw {0},0({1}) ; eieio", 
-            in(reg) value, in(reg) (0xc000_0000 | address), 
+        asm!("stw {0},0({1}) ; eieio",
+            in(reg) value, in(reg) (0xc000_0000 | address),
             options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let _ = value;
+        no_mmio(address)
+    }
 }
 
 /// Read a 16-bit value from an address.
 #[inline(always)]
 pub fn read16(address: u32) -> u16 {
-    // Define an output variable.
-    let mut register;
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define an output variable.
+        let mut register;
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("lhz {0},0({1}) ; sync", 
-            lateout(reg) register,
-            in(reg) (0xc000_0000 | address), 
-            options(nostack));
+        // Run the assembly instruction.
+        unsafe {
+            asm!("lhz {0},0({1}) ; sync",
+                lateout(reg) register,
+                in(reg) (0xc000_0000 | address),
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
     }
 
-    // Return the register value.
-    register
+    #[cfg(not(target_arch = "powerpc"))]
+    no_mmio(address)
 }
 
 /// Write a 16-bit value to an address.
 #[inline(always)]
 pub fn write16(address: u32, value: u16) {
     // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
         asm!("sth {0},0({1}) ; eieio",
-            in(reg) value, in(reg) (0xc000_0000 | address), 
+            in(reg) value, in(reg) (0xc000_0000 | address),
             options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let _ = value;
+        no_mmio(address)
+    }
 }
 
 /// Read a 8-bit value from an address.
 #[inline(always)]
 pub fn read8(address: u32) -> u8 {
-    // Define an output variable.
-    let mut register;
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define an output variable.
+        let mut register;
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("lbz {0},0({1}) ; sync",
-            lateout(reg) register,
-            in(reg) (0xc000_0000 | address),
-            options(nostack));
+        // Run the assembly instruction.
+        unsafe {
+            asm!("lbz {0},0({1}) ; sync",
+                lateout(reg) register,
+                in(reg) (0xc000_0000 | address),
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
     }
 
-    // Return the register value.
-    register
+    #[cfg(not(target_arch = "powerpc"))]
+    no_mmio(address)
 }
 
 /// Write a 8-bit value to an address.
 #[inline(always)]
 pub fn write8(address: u32, value: u8) {
     // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
         asm!("stb {0},0({1}) ; eieio",
             in(reg) value, in(reg) (0xc000_0000 | address),
             options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let _ = value;
+        no_mmio(address)
+    }
 }
 
 /// Write a 32-bit floating value to an address.
 #[inline(always)]
 pub fn writef32(address: u32, value: f32) {
     // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
         asm!("stfs {0},0({1}) ; eieio",
             in(freg) value, in(reg) (0xc000_0000 | address),
             options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let _ = value;
+        no_mmio(address)
+    }
+}
+
+/// Read a 32-bit little-endian value from an address, such as a USB host controller register.
+#[inline(always)]
+pub fn read32_le(address: u32) -> u32 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define an output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        unsafe {
+            asm!("lwbrx {0},0,{1} ; sync",
+                lateout(reg) register,
+                in(reg) (0xc000_0000 | address),
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    no_mmio(address)
+}
+
+/// Write a 32-bit little-endian value to an address.
+#[inline(always)]
+pub fn write32_le(address: u32, value: u32) {
+    // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
+    unsafe {
+        asm!("stwbrx {0},0,{1} ; eieio",
+            in(reg) value, in(reg) (0xc000_0000 | address),
+            options(nostack));
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let _ = value;
+        no_mmio(address)
+    }
+}
+
+/// Read a 16-bit little-endian value from an address.
+#[inline(always)]
+pub fn read16_le(address: u32) -> u16 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define an output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        unsafe {
+            asm!("lhbrx {0},0,{1} ; sync",
+                lateout(reg) register,
+                in(reg) (0xc000_0000 | address),
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    no_mmio(address)
+}
+
+/// Write a 16-bit little-endian value to an address.
+#[inline(always)]
+pub fn write16_le(address: u32, value: u16) {
+    // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
+    unsafe {
+        asm!("sthbrx {0},0,{1} ; eieio",
+            in(reg) value, in(reg) (0xc000_0000 | address),
+            options(nostack));
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        let _ = value;
+        no_mmio(address)
+    }
 }
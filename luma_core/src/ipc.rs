@@ -0,0 +1,210 @@
+//! ``ipc`` module of ``luma_core``.
+//!
+//! Contains a minimal synchronous client for the IOS inter-processor communication of the Wii.
+//!
+//! Every request is sent through the Hollywood IPC registers, then polled until IOS replies, with
+//! interrupts masked for the whole exchange.  This is enough to talk to the ``/dev/stm`` and
+//! ``/dev/es`` drivers, for instance to power off or reset the console.
+
+use crate::cache::{DCFlushRange, DCInvalidateRange};
+use crate::io::{read32, write32};
+use crate::processor;
+
+const BASE: u32 = 0xcd00_0000;
+
+/// Physical address of the request sent to Starlet.
+const PPCMSG: u32 = BASE;
+
+/// IPC control register of Broadway.
+const PPCCTRL: u32 = BASE + 0x04;
+
+/// Physical address of the reply sent by Starlet.
+const ARMMSG: u32 = BASE + 0x08;
+
+/// PPCCTRL[X1], a request is ready for Starlet.
+const CTRL_X1: u32 = 0x01;
+/// PPCCTRL[Y2], Starlet acknowledged the request.
+const CTRL_Y2: u32 = 0x02;
+/// PPCCTRL[Y1], a reply is ready for Broadway.
+const CTRL_Y1: u32 = 0x04;
+/// PPCCTRL[X2], Broadway is done with the reply.
+const CTRL_X2: u32 = 0x08;
+/// PPCCTRL[IY1] and PPCCTRL[IY2], the interrupt enables preserved on every write.
+const CTRL_INTERRUPTS: u32 = 0x30;
+
+/// An IOS error, the negative result of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Error(pub i32);
+
+/// The result of an IOS request.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// The commands of an IOS request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+enum Command {
+    Open = 1,
+    Close = 2,
+    Ioctl = 6,
+    Ioctlv = 7,
+}
+
+/// An IOS request, as read by Starlet.
+#[repr(C, align(32))]
+struct Request {
+    command: u32,
+    result: i32,
+    fd: i32,
+    args: [u32; 5],
+}
+
+/// Get the physical address of a cached or uncached pointer.
+fn physical<T: ?Sized>(pointer: *const T) -> u32 {
+    pointer as *const u8 as u32 & 0x3fff_ffff
+}
+
+/// Send a request, wait for its reply then return its result.
+fn send(command: Command, fd: i32, args: [u32; 5]) -> Result<i32> {
+    let mut request = Request {
+        command: command as u32,
+        result: 0,
+        fd,
+        args,
+    };
+    let request_ptr = &mut request as *mut Request as *const u32;
+    let size = core::mem::size_of::<Request>() as u32;
+
+    processor::critical_section(|| unsafe {
+        DCFlushRange(request_ptr, size);
+
+        write32(PPCMSG, physical(request_ptr));
+        let control = read32(PPCCTRL) & CTRL_INTERRUPTS;
+        write32(PPCCTRL, control | CTRL_X1);
+
+        // Wait for the acknowledgement, then for the reply to this very request.
+        while read32(PPCCTRL) & CTRL_Y2 == 0 {}
+        write32(PPCCTRL, control | CTRL_Y2);
+        loop {
+            while read32(PPCCTRL) & CTRL_Y1 == 0 {}
+            let reply = read32(ARMMSG);
+            write32(PPCCTRL, control | CTRL_Y1);
+            write32(PPCCTRL, control | CTRL_X2);
+            if reply == physical(request_ptr) {
+                break;
+            }
+        }
+
+        DCInvalidateRange(request_ptr, size);
+    });
+
+    if request.result < 0 {
+        Err(Error(request.result))
+    } else {
+        Ok(request.result)
+    }
+}
+
+/// An input or output buffer of an ``ioctlv`` request.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Vector {
+    address: u32,
+    length: u32,
+}
+
+impl Vector {
+    /// Describe an input buffer.
+    pub fn input(buffer: &[u8]) -> Vector {
+        Vector {
+            address: physical(buffer.as_ptr()),
+            length: buffer.len() as u32,
+        }
+    }
+
+    /// Describe an output buffer.
+    ///
+    /// **NOTE**: The buffer should be aligned on a 32 byte boundary and span whole cache blocks,
+    /// since it gets invalidated in the d(ata)-cache once IOS has written it.
+    pub fn output(buffer: &mut [u8]) -> Vector {
+        Vector {
+            address: physical(buffer.as_ptr()),
+            length: buffer.len() as u32,
+        }
+    }
+}
+
+/// An open IOS device or file, closed when dropped.
+pub struct Fd(i32);
+
+impl Fd {
+    /// Open a device or file, ``path`` being null-terminated.
+    ///
+    /// **NOTE**: This function panics if ``path`` isn't null-terminated.
+    pub fn open(path: &[u8], mode: u32) -> Result<Fd> {
+        assert_eq!(path.last(), Some(&0), "IOS paths must be null-terminated");
+        unsafe { DCFlushRange(path.as_ptr() as *const u32, path.len() as u32) };
+        send(Command::Open, 0, [physical(path.as_ptr()), mode, 0, 0, 0]).map(Fd)
+    }
+
+    /// Send an ``ioctl`` request with a single input and output buffer.
+    ///
+    /// **NOTE**: ``output`` should be aligned like the buffers of ``Vector::output``.
+    pub fn ioctl(&self, ioctl: u32, input: &[u8], output: &mut [u8]) -> Result<i32> {
+        unsafe {
+            DCFlushRange(input.as_ptr() as *const u32, input.len() as u32);
+            DCFlushRange(output.as_ptr() as *const u32, output.len() as u32);
+        }
+        let result = send(
+            Command::Ioctl,
+            self.0,
+            [
+                ioctl,
+                physical(input.as_ptr()),
+                input.len() as u32,
+                physical(output.as_ptr()),
+                output.len() as u32,
+            ],
+        );
+        unsafe { DCInvalidateRange(output.as_ptr() as *const u32, output.len() as u32) };
+        result
+    }
+
+    /// Send an ``ioctlv`` request, ``vectors`` holding the inputs followed by the outputs.
+    ///
+    /// # Safety
+    /// Every buffer described by ``vectors`` **MUST** stay alive until this function returns,
+    /// and the output ones **MUST NOT** be accessed meanwhile.
+    pub unsafe fn ioctlv(&self, ioctl: u32, inputs: usize, vectors: &[Vector]) -> Result<i32> {
+        assert!(inputs <= vectors.len());
+        for vector in vectors {
+            DCFlushRange((0x8000_0000 | vector.address) as *const u32, vector.length);
+        }
+        DCFlushRange(
+            vectors.as_ptr() as *const u32,
+            core::mem::size_of_val(vectors) as u32,
+        );
+
+        let result = send(
+            Command::Ioctlv,
+            self.0,
+            [
+                ioctl,
+                inputs as u32,
+                (vectors.len() - inputs) as u32,
+                physical(vectors.as_ptr()),
+                0,
+            ],
+        );
+
+        for vector in &vectors[inputs..] {
+            DCInvalidateRange((0x8000_0000 | vector.address) as *const u32, vector.length);
+        }
+        result
+    }
+}
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        let _ = send(Command::Close, self.0, [0; 5]);
+    }
+}
@@ -3,7 +3,7 @@
 //! This module contains core processor features.
 //!
 //! **NOTE**: This is currently in a very experimental state and is subject to change.
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![allow(unused_attributes)]
 #![feature(global_asm, asm, box_into_boxed_slice, allocator_api)]
 
@@ -15,20 +15,76 @@ pub mod processor;
 // Broadway Register Utilities
 pub mod register;
 
+// Broadway Special-Purpose Register Utilities
+pub mod spr;
+
+// Broadway Block Address Translation Utilities
+pub mod bat;
+
 // Broadway Integer Utilities
 pub mod integer;
 
 // Broadway Load and Store Utilities
 pub mod loadstore;
 
+// Little-Endian Utilities
+pub mod endian;
+
+// Broadway Paired-Single Utilities
+pub mod paired;
+
+// Broadway Graphics Quantization Utilities
+pub mod gqr;
+
+// Broadway Matrix and Vector Math
+pub mod math;
+
 // Broadway I/O Utilities
 pub mod io;
 
+// Broadway Time Base Utilities
+pub mod time;
+
+// Broadway Exception Subsystem
+pub mod exception;
+
+// Processor Interface Interrupt Controller
+pub mod pi;
+
+// Hollywood Interrupt Controller
+#[cfg(not(feature = "gamecube"))]
+pub mod hollywood;
+
+// USB Gecko Utilities
+pub mod gecko;
+
+// IPL Debug UART Utilities
+pub mod uart;
+
+// IOS Inter-Processor Communication
+#[cfg(not(feature = "gamecube"))]
+pub mod ipc;
+
+// Decrementer Alarm Subsystem
+pub mod alarm;
+
+// Interrupt Event Utilities
+pub mod event;
+
 // Broadway Cache Subsystem
 pub mod cache;
 
+// Broadway Write-Gather Pipe Utilities
+pub mod wgpipe;
+
+// Broadway Performance Monitor Utilities
+pub mod pmc;
+
 // Helper functions to allocate aligned memory on the heap
 pub mod allocate;
 
 // VI Subsystem
 pub mod vi;
+
+// Bitmap Font
+pub mod font;
@@ -1,8 +1,12 @@
 //! ``loadstore`` module of ``luma_core``.
 //!
 //! Contains functions for load and store instructions.
+//!
+//! These functions access memory through an absolute 32-bit address, so they only exist on
+//! PowerPC targets.
 
 /// (`lhbrx`) PowerPC Load Instruction
+#[cfg(target_arch = "powerpc")]
 #[inline(always)]
 pub fn lhbrx(base: u32, index: u32) -> u16 {
     // Define a register output variable.
@@ -21,6 +25,7 @@ pub fn lhbrx(base: u32, index: u32) -> u16 {
 }
 
 /// (`lwbrx`) PowerPC Load Instruction
+#[cfg(target_arch = "powerpc")]
 #[inline(always)]
 pub fn lwbrx(base: u32, index: u32) -> u32 {
     // Define a register output variable.
@@ -39,8 +44,9 @@ pub fn lwbrx(base: u32, index: u32) -> u32 {
 }
 
 /// (`sthbrx`) PowerPC Store Instruction
+#[cfg(target_arch = "powerpc")]
 #[inline(always)]
-pub fn sthbrx(base: u32, index: u32, value: u32) {
+pub fn sthbrx(base: u32, index: u32, value: u16) {
     // Run the assembly instruction.
     unsafe {
         asm!("sthbrx {0}, {1}, {2}",
@@ -50,6 +56,7 @@ pub fn sthbrx(base: u32, index: u32, value: u32) {
 }
 
 /// (`stwbrx`) PowerPC Store Instruction
+#[cfg(target_arch = "powerpc")]
 #[inline(always)]
 pub fn stwbrx(base: u32, index: u32, value: u32) {
     // Run the assembly instruction.
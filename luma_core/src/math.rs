@@ -0,0 +1,936 @@
+//! ``math`` module of ``luma_core``.
+//!
+//! Contains the vectors, quaternions and matrices used for 3D rendering and game logic.
+//!
+//! Matrices are stored row-major, following the GX conventions: a ``Mtx34`` is an affine
+//! transform whose implicit last row is ``[0, 0, 0, 1]``, and the projections of ``Mtx44`` map
+//! depth to ``[-1, 0]``.  Concatenation and point transforms, the hot paths, use paired-single
+//! assembly on Broadway, while other targets use the scalar implementation of ``reference``.
+
+use crate::paired::f32x2;
+use core::ops::{Add, Mul, Neg, Sub};
+
+#[cfg(target_arch = "powerpc")]
+global_asm!(include_str!("../asm/math.S"));
+
+// Load the paired-single matrix functions from global assembly.
+#[cfg(target_arch = "powerpc")]
+extern "C" {
+    fn PSMTXConcat(a: *const Mtx34, b: *const Mtx34, ab: *mut Mtx34);
+    fn PSMTXMultVec(m: *const Mtx34, src: *const Vec3, dst: *mut Vec3);
+    fn PSMTXMultVecArray(m: *const Mtx34, src: *const Vec3, dst: *mut Vec3, count: u32);
+    fn PSMTXMultVecSR(m: *const Mtx34, src: *const Vec3, dst: *mut Vec3);
+}
+
+/// Archimedes’ constant (π).
+pub const PI: f32 = core::f32::consts::PI;
+
+/// Convert an angle from degrees to radians.
+pub fn to_radians(degrees: f32) -> f32 {
+    degrees * (PI / 180.0)
+}
+
+/// Reciprocal square root, from the ``ps_rsqrte`` estimate refined by Newton-Raphson.
+pub fn rsqrt(x: f32) -> f32 {
+    let mut y = f32x2::splat(x).ps_rsqrte().ps0();
+    for _ in 0..2 {
+        y *= 1.5 - 0.5 * x * y * y;
+    }
+    y
+}
+
+/// Square root, zero for negative inputs.
+pub fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    x * rsqrt(x)
+}
+
+/// Sine and cosine of an angle in radians.
+pub fn sin_cos(angle: f32) -> (f32, f32) {
+    // Reduce to [-π/4, π/4] around a multiple of π/2, in double precision.
+    let angle = angle as f64;
+    let half = if angle < 0.0 { -0.5 } else { 0.5 };
+    let quadrant = (angle * core::f64::consts::FRAC_2_PI + half) as i32;
+    let r = (angle - quadrant as f64 * core::f64::consts::FRAC_PI_2) as f32;
+
+    // Taylor polynomials, accurate to single precision on the reduced range.
+    let r2 = r * r;
+    let sin = r * (1.0 - r2 / 6.0 * (1.0 - r2 / 20.0 * (1.0 - r2 / 42.0)));
+    let cos = 1.0 - r2 / 2.0 * (1.0 - r2 / 12.0 * (1.0 - r2 / 30.0 * (1.0 - r2 / 56.0)));
+    match quadrant & 3 {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
+
+/// A 3D vector, or point.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C)]
+pub struct Vec3 {
+    /// X component.
+    pub x: f32,
+    /// Y component.
+    pub y: f32,
+    /// Z component.
+    pub z: f32,
+}
+
+impl Vec3 {
+    /// The null vector.
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+
+    /// Create a vector from its components.
+    pub const fn new(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    /// Dot product of two vectors.
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Cross product of two vectors.
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Squared length of this vector.
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+
+    /// Length of this vector.
+    pub fn length(self) -> f32 {
+        sqrt(self.length_squared())
+    }
+
+    /// Get a unit vector of the same direction, the null vector stays null.
+    pub fn normalize(self) -> Vec3 {
+        let length_squared = self.length_squared();
+        if length_squared == 0.0 {
+            return self;
+        }
+        self * rsqrt(length_squared)
+    }
+
+    /// Scale this vector by the factors of ``other``, component by component.
+    pub fn scale(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x * other.x, self.y * other.y, self.z * other.z)
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: f32) -> Vec3 {
+        Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// A rotation quaternion, ``w`` being the real part.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Quat {
+    /// First imaginary component.
+    pub x: f32,
+    /// Second imaginary component.
+    pub y: f32,
+    /// Third imaginary component.
+    pub z: f32,
+    /// Real component.
+    pub w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Quat {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    /// The quaternion of no rotation.
+    pub const IDENTITY: Quat = Quat::new(0.0, 0.0, 0.0, 1.0);
+
+    /// Create a quaternion from its components.
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Quat {
+        Quat { x, y, z, w }
+    }
+
+    /// Create the rotation of ``angle`` radians around ``axis``.
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Quat {
+        let (sin, cos) = sin_cos(angle * 0.5);
+        let axis = axis.normalize() * sin;
+        Quat::new(axis.x, axis.y, axis.z, cos)
+    }
+
+    /// Dot product of two quaternions.
+    pub fn dot(self, other: Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    /// Get a unit quaternion of the same rotation.
+    pub fn normalize(self) -> Quat {
+        let norm = self.dot(self);
+        if norm == 0.0 {
+            return Quat::IDENTITY;
+        }
+        let scale = rsqrt(norm);
+        Quat::new(
+            self.x * scale,
+            self.y * scale,
+            self.z * scale,
+            self.w * scale,
+        )
+    }
+
+    /// Get the conjugate, which is the inverse rotation of a unit quaternion.
+    pub fn conjugate(self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    /// Get the inverse of this quaternion, ``None`` for the null quaternion.
+    pub fn inverse(self) -> Option<Quat> {
+        let norm = self.dot(self);
+        if norm == 0.0 {
+            return None;
+        }
+        let conjugate = self.conjugate();
+        Some(Quat::new(
+            conjugate.x / norm,
+            conjugate.y / norm,
+            conjugate.z / norm,
+            conjugate.w / norm,
+        ))
+    }
+
+    /// Normalized linear interpolation between two rotations, along the shortest path.
+    pub fn nlerp(self, other: Quat, t: f32) -> Quat {
+        let other = if self.dot(other) < 0.0 {
+            Quat::new(-other.x, -other.y, -other.z, -other.w)
+        } else {
+            other
+        };
+        Quat::new(
+            self.x + (other.x - self.x) * t,
+            self.y + (other.y - self.y) * t,
+            self.z + (other.z - self.z) * t,
+            self.w + (other.w - self.w) * t,
+        )
+        .normalize()
+    }
+
+    /// Rotate a vector by this unit quaternion.
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    /// Compose two rotations, ``rhs`` being applied first.
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+/// A 3×4 affine transform matrix, as loaded into the GX position and normal matrices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Mtx34(pub [[f32; 4]; 3]);
+
+impl Default for Mtx34 {
+    fn default() -> Mtx34 {
+        Mtx34::IDENTITY
+    }
+}
+
+impl Mtx34 {
+    /// The identity transform.
+    pub const IDENTITY: Mtx34 = Mtx34([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ]);
+
+    /// Create a translation.
+    pub fn translation(t: Vec3) -> Mtx34 {
+        Mtx34([
+            [1.0, 0.0, 0.0, t.x],
+            [0.0, 1.0, 0.0, t.y],
+            [0.0, 0.0, 1.0, t.z],
+        ])
+    }
+
+    /// Create a scale along each axis.
+    pub fn scale(s: Vec3) -> Mtx34 {
+        Mtx34([
+            [s.x, 0.0, 0.0, 0.0],
+            [0.0, s.y, 0.0, 0.0],
+            [0.0, 0.0, s.z, 0.0],
+        ])
+    }
+
+    /// Create a rotation of ``angle`` radians around the X axis.
+    pub fn rotation_x(angle: f32) -> Mtx34 {
+        let (sin, cos) = sin_cos(angle);
+        Mtx34([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, -sin, 0.0],
+            [0.0, sin, cos, 0.0],
+        ])
+    }
+
+    /// Create a rotation of ``angle`` radians around the Y axis.
+    pub fn rotation_y(angle: f32) -> Mtx34 {
+        let (sin, cos) = sin_cos(angle);
+        Mtx34([
+            [cos, 0.0, sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin, 0.0, cos, 0.0],
+        ])
+    }
+
+    /// Create a rotation of ``angle`` radians around the Z axis.
+    pub fn rotation_z(angle: f32) -> Mtx34 {
+        let (sin, cos) = sin_cos(angle);
+        Mtx34([
+            [cos, -sin, 0.0, 0.0],
+            [sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ])
+    }
+
+    /// Create a rotation of ``angle`` radians around ``axis``.
+    pub fn rotation(axis: Vec3, angle: f32) -> Mtx34 {
+        Mtx34::from_quat(Quat::from_axis_angle(axis, angle))
+    }
+
+    /// Create the rotation of a quaternion, which doesn't need to be normalized.
+    pub fn from_quat(q: Quat) -> Mtx34 {
+        let norm = q.dot(q);
+        let s = if norm == 0.0 { 0.0 } else { 2.0 / norm };
+        let (xs, ys, zs) = (q.x * s, q.y * s, q.z * s);
+        let (wx, wy, wz) = (q.w * xs, q.w * ys, q.w * zs);
+        let (xx, xy, xz) = (q.x * xs, q.x * ys, q.x * zs);
+        let (yy, yz, zz) = (q.y * ys, q.y * zs, q.z * zs);
+        Mtx34([
+            [1.0 - (yy + zz), xy - wz, xz + wy, 0.0],
+            [xy + wz, 1.0 - (xx + zz), yz - wx, 0.0],
+            [xz - wy, yz + wx, 1.0 - (xx + yy), 0.0],
+        ])
+    }
+
+    /// Create a camera matrix at ``eye``, looking at ``target`` with ``up`` upwards.
+    pub fn look_at(eye: Vec3, up: Vec3, target: Vec3) -> Mtx34 {
+        let look = (eye - target).normalize();
+        let right = up.cross(look).normalize();
+        let up = look.cross(right);
+        Mtx34([
+            [right.x, right.y, right.z, -eye.dot(right)],
+            [up.x, up.y, up.z, -eye.dot(up)],
+            [look.x, look.y, look.z, -eye.dot(look)],
+        ])
+    }
+
+    /// Get the translation part of this transform.
+    pub fn translation_part(&self) -> Vec3 {
+        Vec3::new(self.0[0][3], self.0[1][3], self.0[2][3])
+    }
+
+    /// Concatenate two transforms, ``other`` being applied first.
+    pub fn concat(&self, other: &Mtx34) -> Mtx34 {
+        #[cfg(target_arch = "powerpc")]
+        {
+            let mut ab = Mtx34::IDENTITY;
+            unsafe { PSMTXConcat(self, other, &mut ab) };
+            ab
+        }
+
+        #[cfg(not(target_arch = "powerpc"))]
+        reference::concat(self, other)
+    }
+
+    /// Transform a point, translation included.
+    pub fn transform_point(&self, v: Vec3) -> Vec3 {
+        #[cfg(target_arch = "powerpc")]
+        {
+            let mut dst = Vec3::ZERO;
+            unsafe { PSMTXMultVec(self, &v, &mut dst) };
+            dst
+        }
+
+        #[cfg(not(target_arch = "powerpc"))]
+        reference::transform_point(self, v)
+    }
+
+    /// Transform a vector by the rotation and scale only, leaving out the translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        #[cfg(target_arch = "powerpc")]
+        {
+            let mut dst = Vec3::ZERO;
+            unsafe { PSMTXMultVecSR(self, &v, &mut dst) };
+            dst
+        }
+
+        #[cfg(not(target_arch = "powerpc"))]
+        reference::transform_vector(self, v)
+    }
+
+    /// Transform every point of ``src`` into ``dst``.
+    ///
+    /// **NOTE**: This function panics if both slices differ in length.
+    pub fn transform_points(&self, src: &[Vec3], dst: &mut [Vec3]) {
+        assert_eq!(src.len(), dst.len(), "Mismatched point slices");
+
+        #[cfg(target_arch = "powerpc")]
+        unsafe {
+            PSMTXMultVecArray(self, src.as_ptr(), dst.as_mut_ptr(), src.len() as u32)
+        };
+
+        #[cfg(not(target_arch = "powerpc"))]
+        for (src, dst) in src.iter().zip(dst.iter_mut()) {
+            *dst = reference::transform_point(self, *src);
+        }
+    }
+
+    /// Transform every point of ``points`` in place.
+    pub fn transform_points_in_place(&self, points: &mut [Vec3]) {
+        #[cfg(target_arch = "powerpc")]
+        unsafe {
+            let count = points.len() as u32;
+            let points = points.as_mut_ptr();
+            PSMTXMultVecArray(self, points, points, count)
+        };
+
+        #[cfg(not(target_arch = "powerpc"))]
+        for point in points.iter_mut() {
+            *point = reference::transform_point(self, *point);
+        }
+    }
+
+    /// Get the transpose of the 3×3 part, with a null translation.
+    pub fn transpose(&self) -> Mtx34 {
+        let m = &self.0;
+        Mtx34([
+            [m[0][0], m[1][0], m[2][0], 0.0],
+            [m[0][1], m[1][1], m[2][1], 0.0],
+            [m[0][2], m[1][2], m[2][2], 0.0],
+        ])
+    }
+
+    /// Get the inverse of the 3×3 part, ``None`` if it is singular.
+    fn inverse_3x3(&self) -> Option<[[f32; 3]; 3]> {
+        let m = &self.0;
+        let cofactor = [
+            [
+                m[1][1] * m[2][2] - m[2][1] * m[1][2],
+                m[2][1] * m[0][2] - m[0][1] * m[2][2],
+                m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            ],
+            [
+                m[2][0] * m[1][2] - m[1][0] * m[2][2],
+                m[0][0] * m[2][2] - m[2][0] * m[0][2],
+                m[1][0] * m[0][2] - m[0][0] * m[1][2],
+            ],
+            [
+                m[1][0] * m[2][1] - m[2][0] * m[1][1],
+                m[2][0] * m[0][1] - m[0][0] * m[2][1],
+                m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            ],
+        ];
+        let det = m[0][0] * cofactor[0][0] + m[0][1] * cofactor[1][0] + m[0][2] * cofactor[2][0];
+        if det == 0.0 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let mut inverse = [[0.0; 3]; 3];
+        for (row, cofactors) in inverse.iter_mut().zip(cofactor.iter()) {
+            for (value, cofactor) in row.iter_mut().zip(cofactors.iter()) {
+                *value = cofactor * inv_det;
+            }
+        }
+        Some(inverse)
+    }
+
+    /// Get the inverse transform, ``None`` if this one is singular.
+    pub fn inverse(&self) -> Option<Mtx34> {
+        let i = self.inverse_3x3()?;
+        let t = self.translation_part();
+        let row = |r: usize| {
+            let translation = -(i[r][0] * t.x + i[r][1] * t.y + i[r][2] * t.z);
+            [i[r][0], i[r][1], i[r][2], translation]
+        };
+        Some(Mtx34([row(0), row(1), row(2)]))
+    }
+
+    /// Get the inverse-transpose of the 3×3 part, with a null translation, which transforms the
+    /// normals.  ``None`` if this transform is singular.
+    pub fn inverse_transpose(&self) -> Option<Mtx34> {
+        let i = self.inverse_3x3()?;
+        Some(Mtx34([
+            [i[0][0], i[1][0], i[2][0], 0.0],
+            [i[0][1], i[1][1], i[2][1], 0.0],
+            [i[0][2], i[1][2], i[2][2], 0.0],
+        ]))
+    }
+
+    /// Extend this transform to a 4×4 matrix.
+    pub fn to_mtx44(&self) -> Mtx44 {
+        let m = &self.0;
+        Mtx44([m[0], m[1], m[2], [0.0, 0.0, 0.0, 1.0]])
+    }
+}
+
+impl Mul for Mtx34 {
+    type Output = Mtx34;
+
+    fn mul(self, rhs: Mtx34) -> Mtx34 {
+        self.concat(&rhs)
+    }
+}
+
+impl Mul<Vec3> for Mtx34 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        self.transform_point(rhs)
+    }
+}
+
+/// A 4×4 matrix, as loaded into the GX projection matrix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Mtx44(pub [[f32; 4]; 4]);
+
+impl Default for Mtx44 {
+    fn default() -> Mtx44 {
+        Mtx44::IDENTITY
+    }
+}
+
+impl Mtx44 {
+    /// The identity matrix.
+    pub const IDENTITY: Mtx44 = Mtx44([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    /// Create a perspective projection, from the vertical field of view in degrees, the aspect
+    /// ratio and the distances of the near and far clipping planes.
+    pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> Mtx44 {
+        let (sin, cos) = sin_cos(to_radians(fovy) * 0.5);
+        let cot = cos / sin;
+        let depth = 1.0 / (far - near);
+        Mtx44([
+            [cot / aspect, 0.0, 0.0, 0.0],
+            [0.0, cot, 0.0, 0.0],
+            [0.0, 0.0, -near * depth, -(far * near) * depth],
+            [0.0, 0.0, -1.0, 0.0],
+        ])
+    }
+
+    /// Create a perspective projection from the edges of the near clipping plane.
+    pub fn frustum(top: f32, bottom: f32, left: f32, right: f32, near: f32, far: f32) -> Mtx44 {
+        let width = 1.0 / (right - left);
+        let height = 1.0 / (top - bottom);
+        let depth = 1.0 / (far - near);
+        Mtx44([
+            [2.0 * near * width, 0.0, (right + left) * width, 0.0],
+            [0.0, 2.0 * near * height, (top + bottom) * height, 0.0],
+            [0.0, 0.0, -near * depth, -(far * near) * depth],
+            [0.0, 0.0, -1.0, 0.0],
+        ])
+    }
+
+    /// Create an orthographic projection from the edges of the view volume.
+    pub fn ortho(top: f32, bottom: f32, left: f32, right: f32, near: f32, far: f32) -> Mtx44 {
+        let width = 1.0 / (right - left);
+        let height = 1.0 / (top - bottom);
+        let depth = 1.0 / (far - near);
+        Mtx44([
+            [2.0 * width, 0.0, 0.0, -(right + left) * width],
+            [0.0, 2.0 * height, 0.0, -(top + bottom) * height],
+            [0.0, 0.0, -depth, -far * depth],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Concatenate two matrices, ``other`` being applied first.
+    pub fn concat(&self, other: &Mtx44) -> Mtx44 {
+        let mut ab = [[0.0; 4]; 4];
+        for (r, row) in ab.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.0[r][k] * other.0[k][c]).sum();
+            }
+        }
+        Mtx44(ab)
+    }
+
+    /// Get the transpose of this matrix.
+    pub fn transpose(&self) -> Mtx44 {
+        let mut t = [[0.0; 4]; 4];
+        for (r, row) in t.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = self.0[c][r];
+            }
+        }
+        Mtx44(t)
+    }
+
+    /// Get the inverse of this matrix, ``None`` if it is singular.
+    pub fn inverse(&self) -> Option<Mtx44> {
+        let m = &self.0;
+
+        // 2×2 determinants of the top and bottom halves.
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det == 0.0 {
+            return None;
+        }
+        let i = 1.0 / det;
+
+        Some(Mtx44([
+            [
+                (m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * i,
+                (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * i,
+                (m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * i,
+                (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * i,
+            ],
+            [
+                (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * i,
+                (m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * i,
+                (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * i,
+                (m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * i,
+            ],
+            [
+                (m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * i,
+                (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * i,
+                (m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * i,
+                (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * i,
+            ],
+            [
+                (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * i,
+                (m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * i,
+                (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * i,
+                (m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * i,
+            ],
+        ]))
+    }
+}
+
+impl Mul for Mtx44 {
+    type Output = Mtx44;
+
+    fn mul(self, rhs: Mtx44) -> Mtx44 {
+        self.concat(&rhs)
+    }
+}
+
+/// Scalar implementation of the paired-single hot paths, used on targets other than Broadway
+/// and as a reference to check them against.
+pub mod reference {
+    use super::{Mtx34, Vec3};
+
+    /// Concatenate two transforms, ``b`` being applied first.
+    pub fn concat(a: &Mtx34, b: &Mtx34) -> Mtx34 {
+        let (a, b) = (&a.0, &b.0);
+        let mut ab = [[0.0; 4]; 3];
+        for (r, row) in ab.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = a[r][0] * b[0][c] + a[r][1] * b[1][c] + a[r][2] * b[2][c];
+                if c == 3 {
+                    *value += a[r][3];
+                }
+            }
+        }
+        Mtx34(ab)
+    }
+
+    /// Transform a point, translation included.
+    pub fn transform_point(m: &Mtx34, v: Vec3) -> Vec3 {
+        let row = |r: [f32; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z + r[3];
+        Vec3::new(row(m.0[0]), row(m.0[1]), row(m.0[2]))
+    }
+
+    /// Transform a vector by the rotation and scale only.
+    pub fn transform_vector(m: &Mtx34, v: Vec3) -> Vec3 {
+        let row = |r: [f32; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vec3::new(row(m.0[0]), row(m.0[1]), row(m.0[2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!(
+            (a - b).abs() <= EPSILON * b.abs().max(1.0),
+            "{} != {}",
+            a,
+            b
+        );
+    }
+
+    fn assert_vec_close(a: Vec3, b: Vec3) {
+        assert_close(a.x, b.x);
+        assert_close(a.y, b.y);
+        assert_close(a.z, b.z);
+    }
+
+    fn assert_rows_close(a: &[[f32; 4]], b: &[[f32; 4]]) {
+        for (a, b) in a.iter().zip(b.iter()) {
+            for (&a, &b) in a.iter().zip(b.iter()) {
+                assert_close(a, b);
+            }
+        }
+    }
+
+    /// A transform with rotation, non-uniform scale and translation.
+    fn transform() -> Mtx34 {
+        Mtx34::translation(Vec3::new(1.0, -2.0, 3.0))
+            .concat(&Mtx34::rotation(Vec3::new(1.0, 2.0, 3.0), 0.7))
+            .concat(&Mtx34::scale(Vec3::new(2.0, 0.5, 3.0)))
+    }
+
+    /// Apply a projection to a point, returning its clip coordinates.
+    fn project(m: &Mtx44, v: Vec3) -> [f32; 4] {
+        let row = |r: [f32; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z + r[3];
+        [row(m.0[0]), row(m.0[1]), row(m.0[2]), row(m.0[3])]
+    }
+
+    #[test]
+    fn scalars() {
+        assert_close(sqrt(16.0), 4.0);
+        assert_eq!(sqrt(-1.0), 0.0);
+        assert_close(rsqrt(0.25), 2.0);
+        for n in -40..=40 {
+            let angle = n as f32 * 0.3;
+            let (sin, cos) = sin_cos(angle);
+            assert_close(sin, (angle as f64).sin() as f32);
+            assert_close(cos, (angle as f64).cos() as f32);
+        }
+    }
+
+    #[test]
+    fn concat_matches_reference() {
+        let a = transform();
+        let b = Mtx34::rotation_x(1.2).concat(&Mtx34::translation(Vec3::new(4.0, 5.0, 6.0)));
+        assert_rows_close(&a.concat(&b).0, &reference::concat(&a, &b).0);
+        assert_rows_close(&(a * b).0, &reference::concat(&a, &b).0);
+
+        let t = Mtx34::translation(Vec3::new(1.0, 2.0, 3.0));
+        let s = Mtx34::scale(Vec3::new(2.0, 3.0, 4.0));
+        let expected = [
+            [2.0, 0.0, 0.0, 2.0],
+            [0.0, 3.0, 0.0, 6.0],
+            [0.0, 0.0, 4.0, 12.0],
+        ];
+        assert_eq!(reference::concat(&s, &t).0, expected);
+        assert_eq!(s.concat(&t).0, expected);
+    }
+
+    #[test]
+    fn transforms_match_reference() {
+        let m = transform();
+        let points = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-3.0, 2.5, 7.0),
+            Vec3::new(0.25, -8.0, 0.5),
+        ];
+        for &v in points.iter() {
+            assert_vec_close(m.transform_point(v), reference::transform_point(&m, v));
+            assert_vec_close(m.transform_vector(v), reference::transform_vector(&m, v));
+            assert_vec_close(m * v, reference::transform_point(&m, v));
+        }
+
+        let mut dst = [Vec3::ZERO; 3];
+        m.transform_points(&points, &mut dst);
+        let mut in_place = points;
+        m.transform_points_in_place(&mut in_place);
+        for ((&v, &dst), &in_place) in points.iter().zip(dst.iter()).zip(in_place.iter()) {
+            assert_vec_close(dst, reference::transform_point(&m, v));
+            assert_vec_close(in_place, reference::transform_point(&m, v));
+        }
+
+        let t = Mtx34::translation(Vec3::new(1.0, 2.0, 3.0));
+        let v = Vec3::new(1.0, 1.0, 1.0);
+        assert_eq!(reference::transform_point(&t, v), Vec3::new(2.0, 3.0, 4.0));
+        assert_eq!(reference::transform_vector(&t, v), v);
+    }
+
+    #[test]
+    fn mtx34_inverse() {
+        let m = transform();
+        let inverse = m.inverse().unwrap();
+        assert_rows_close(&m.concat(&inverse).0, &Mtx34::IDENTITY.0);
+        assert_rows_close(&inverse.concat(&m).0, &Mtx34::IDENTITY.0);
+        assert_rows_close(&m.inverse_transpose().unwrap().0, &inverse.transpose().0);
+
+        let v = Vec3::new(3.0, -1.0, 2.0);
+        assert_vec_close(inverse.transform_point(m.transform_point(v)), v);
+        assert_eq!(Mtx34::scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+    }
+
+    #[test]
+    fn mtx44_inverse() {
+        let p = Mtx44::perspective(60.0, 4.0 / 3.0, 1.0, 100.0);
+        let inverse = p.inverse().unwrap();
+        assert_rows_close(&p.concat(&inverse).0, &Mtx44::IDENTITY.0);
+        assert_rows_close(&(inverse * p).0, &Mtx44::IDENTITY.0);
+
+        let m = transform();
+        assert_rows_close(
+            &m.to_mtx44().inverse().unwrap().0,
+            &m.inverse().unwrap().to_mtx44().0,
+        );
+        assert_eq!(p.transpose().transpose(), p);
+        assert_eq!(Mtx44([[0.0; 4]; 4]).inverse(), None);
+    }
+
+    #[test]
+    fn perspective() {
+        let p = Mtx44::perspective(90.0, 2.0, 1.0, 100.0);
+        assert_close(p.0[0][0], 0.5);
+        assert_close(p.0[1][1], 1.0);
+
+        // The near plane maps to -1 and the far plane to 0.
+        let near = project(&p, Vec3::new(0.5, 1.0, -1.0));
+        assert_close(near[3], 1.0);
+        assert_close(near[0] / near[3], 0.25);
+        assert_close(near[1] / near[3], 1.0);
+        assert_close(near[2] / near[3], -1.0);
+        let far = project(&p, Vec3::new(0.0, 0.0, -100.0));
+        assert_close(far[2] / far[3], 0.0);
+
+        let f = Mtx44::frustum(1.0, -1.0, -2.0, 2.0, 1.0, 100.0);
+        assert_rows_close(&f.0, &p.0);
+    }
+
+    #[test]
+    fn ortho() {
+        let o = Mtx44::ortho(1.0, -1.0, -2.0, 2.0, 1.0, 10.0);
+        assert_eq!(
+            project(&o, Vec3::new(2.0, 1.0, -1.0)),
+            [1.0, 1.0, -1.0, 1.0]
+        );
+        assert_eq!(
+            project(&o, Vec3::new(-2.0, -1.0, -10.0)),
+            [-1.0, -1.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn look_at() {
+        let m = Mtx34::look_at(
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::ZERO,
+        );
+        assert_rows_close(&m.0, &Mtx34::translation(Vec3::new(0.0, 0.0, -5.0)).0);
+
+        let eye = Vec3::new(3.0, 4.0, 5.0);
+        let target = Vec3::new(1.0, -2.0, 0.0);
+        let m = Mtx34::look_at(eye, Vec3::new(0.0, 1.0, 0.0), target);
+        assert_vec_close(m.transform_point(eye), Vec3::ZERO);
+        assert_vec_close(
+            m.transform_point(target),
+            Vec3::new(0.0, 0.0, -(eye - target).length()),
+        );
+
+        // The rotation part is orthonormal.
+        let rotation = m.transpose().transpose();
+        assert_rows_close(&rotation.concat(&m.transpose()).0, &Mtx34::IDENTITY.0);
+    }
+
+    #[test]
+    fn quat() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let q = Quat::from_axis_angle(z, PI / 2.0);
+        assert_vec_close(q.rotate(x), Vec3::new(0.0, 1.0, 0.0));
+
+        let r = Quat::from_axis_angle(Vec3::new(1.0, 2.0, 3.0), 0.7);
+        let v = Vec3::new(-3.0, 2.5, 7.0);
+        assert_vec_close((q * r).rotate(v), q.rotate(r.rotate(v)));
+        assert_vec_close(Mtx34::from_quat(r).transform_vector(v), r.rotate(v));
+        assert_vec_close(
+            Mtx34::rotation_z(PI / 2.0).transform_vector(x),
+            Mtx34::from_quat(q).transform_vector(x),
+        );
+
+        let identity = r * r.inverse().unwrap();
+        assert_close(identity.x, 0.0);
+        assert_close(identity.y, 0.0);
+        assert_close(identity.z, 0.0);
+        assert_close(identity.w, 1.0);
+        let inverse = r.inverse().unwrap();
+        assert_close(inverse.dot(r.conjugate()), 1.0);
+        assert_eq!(Quat::new(0.0, 0.0, 0.0, 0.0).inverse(), None);
+        assert_eq!(Quat::new(0.0, 0.0, 0.0, 0.0).normalize(), Quat::IDENTITY);
+
+        // Halfway along the shortest path, even from the opposite quaternion.
+        let half = Quat::from_axis_angle(z, PI / 4.0);
+        let opposite = Quat::new(-q.x, -q.y, -q.z, -q.w);
+        for &end in [q, opposite].iter() {
+            assert_close(Quat::IDENTITY.nlerp(end, 0.5).dot(half), 1.0);
+        }
+    }
+}
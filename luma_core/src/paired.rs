@@ -0,0 +1,475 @@
+//! ``paired`` module of ``luma_core``.
+//!
+//! Contains the ``f32x2`` type, a pair of single-precision floats operated on by Broadway's
+//! paired-single instructions.
+//!
+//! Each operation loads its operands into paired-single registers through GQR0, runs the
+//! instruction and stores the result back, so GQR0 has to be left unscaled and unquantized as
+//! ``InitPS`` sets it.  Other targets use a portable implementation of every instruction, so that
+//! code using this module also runs on the host.
+//!
+//! **NOTE**: The assembler doesn't know the paired-single mnemonics, so they get encoded by hand.
+
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A pair of ``f32``, ``ps0`` and ``ps1``, as held by a paired-single register.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C, align(8))]
+pub struct f32x2(pub [f32; 2]);
+
+/// Run a paired-single instruction on ``a``, ``b`` and ``c``, given its operand fields.
+#[cfg(target_arch = "powerpc")]
+macro_rules! paired {
+    ($fields:literal, $a:expr, $b:expr, $c:expr, $fallback:expr) => {{
+        let a: f32x2 = $a;
+        let b: f32x2 = $b;
+        let c: f32x2 = $c;
+        let mut d = f32x2::default();
+
+        // Run the assembly instruction, between psq_l and psq_st through GQR0.
+        unsafe {
+            asm!(".long (56<<26)|({fa}<<21)|({pa}<<16)",
+                 ".long (56<<26)|({fb}<<21)|({pb}<<16)",
+                 ".long (56<<26)|({fc}<<21)|({pc}<<16)",
+                 concat!(".long (4<<26)|({fd}<<21)|", $fields),
+                 ".long (60<<26)|({fd}<<21)|({pd}<<16)",
+                pa = in(reg_nonzero) &a,
+                pb = in(reg_nonzero) &b,
+                pc = in(reg_nonzero) &c,
+                pd = in(reg_nonzero) &mut d,
+                fa = out(freg) _,
+                fb = out(freg) _,
+                fc = out(freg) _,
+                fd = out(freg) _,
+                options(nostack));
+        }
+
+        // Return the register value.
+        d
+    }};
+}
+
+/// Run the portable implementation of a paired-single instruction on ``a``, ``b`` and ``c``.
+#[cfg(not(target_arch = "powerpc"))]
+macro_rules! paired {
+    ($fields:literal, $a:expr, $b:expr, $c:expr, $fallback:expr) => {{
+        let fallback: fn([f32; 2], [f32; 2], [f32; 2]) -> [f32; 2] = $fallback;
+        f32x2(fallback($a.0, $b.0, $c.0))
+    }};
+}
+
+/// Reciprocal square root of a single ``f32``, for the portable ``ps_rsqrte``.
+#[cfg(not(target_arch = "powerpc"))]
+fn rsqrt(x: f32) -> f32 {
+    if x.is_nan() || x < 0.0 {
+        return f32::NAN;
+    }
+    if x == 0.0 {
+        return 1.0 / x;
+    }
+    if x.is_infinite() {
+        return 0.0;
+    }
+
+    // Initial estimate from the exponent, refined with Newton-Raphson iterations.
+    let mut y = f32::from_bits(0x5f37_5a86 - (x.to_bits() >> 1));
+    for _ in 0..4 {
+        y *= 1.5 - 0.5 * x * y * y;
+    }
+    y
+}
+
+impl f32x2 {
+    /// Create a pair from its two halves.
+    pub const fn new(ps0: f32, ps1: f32) -> f32x2 {
+        f32x2([ps0, ps1])
+    }
+
+    /// Create a pair with both halves set to ``value``.
+    pub const fn splat(value: f32) -> f32x2 {
+        f32x2([value, value])
+    }
+
+    /// Get the first half.
+    pub fn ps0(self) -> f32 {
+        self.0[0]
+    }
+
+    /// Get the second half.
+    pub fn ps1(self) -> f32 {
+        self.0[1]
+    }
+
+    /// (`ps_add`) Paired-Single Add, ``self + b``.
+    #[inline(always)]
+    pub fn ps_add(self, b: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fb}<<11)|(21<<1)", self, b, b, |a, b, _| {
+            [a[0] + b[0], a[1] + b[1]]
+        })
+    }
+
+    /// (`ps_sub`) Paired-Single Subtract, ``self - b``.
+    #[inline(always)]
+    pub fn ps_sub(self, b: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fb}<<11)|(20<<1)", self, b, b, |a, b, _| {
+            [a[0] - b[0], a[1] - b[1]]
+        })
+    }
+
+    /// (`ps_mul`) Paired-Single Multiply, ``self × c``.
+    #[inline(always)]
+    pub fn ps_mul(self, c: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fc}<<6)|(25<<1)", self, c, c, |a, _, c| {
+            [a[0] * c[0], a[1] * c[1]]
+        })
+    }
+
+    /// (`ps_div`) Paired-Single Divide, ``self / b``.
+    #[inline(always)]
+    pub fn ps_div(self, b: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fb}<<11)|(18<<1)", self, b, b, |a, b, _| {
+            [a[0] / b[0], a[1] / b[1]]
+        })
+    }
+
+    /// (`ps_madd`) Paired-Single Multiply-Add, ``self × c + b``.
+    #[inline(always)]
+    pub fn ps_madd(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(29<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [a[0] * c[0] + b[0], a[1] * c[1] + b[1]] }
+        )
+    }
+
+    /// (`ps_msub`) Paired-Single Multiply-Subtract, ``self × c - b``.
+    #[inline(always)]
+    pub fn ps_msub(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(28<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [a[0] * c[0] - b[0], a[1] * c[1] - b[1]] }
+        )
+    }
+
+    /// (`ps_nmadd`) Paired-Single Negative Multiply-Add, ``-(self × c + b)``.
+    #[inline(always)]
+    pub fn ps_nmadd(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(31<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [-(a[0] * c[0] + b[0]), -(a[1] * c[1] + b[1])] }
+        )
+    }
+
+    /// (`ps_nmsub`) Paired-Single Negative Multiply-Subtract, ``-(self × c - b)``.
+    #[inline(always)]
+    pub fn ps_nmsub(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(30<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [-(a[0] * c[0] - b[0]), -(a[1] * c[1] - b[1])] }
+        )
+    }
+
+    /// (`ps_muls0`) Paired-Single Multiply Scalar High, ``self × c.ps0``.
+    #[inline(always)]
+    pub fn ps_muls0(self, c: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fc}<<6)|(12<<1)", self, c, c, |a, _, c| {
+            [a[0] * c[0], a[1] * c[0]]
+        })
+    }
+
+    /// (`ps_muls1`) Paired-Single Multiply Scalar Low, ``self × c.ps1``.
+    #[inline(always)]
+    pub fn ps_muls1(self, c: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fc}<<6)|(13<<1)", self, c, c, |a, _, c| {
+            [a[0] * c[1], a[1] * c[1]]
+        })
+    }
+
+    /// (`ps_madds0`) Paired-Single Multiply-Add Scalar High, ``self × c.ps0 + b``.
+    #[inline(always)]
+    pub fn ps_madds0(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(14<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [a[0] * c[0] + b[0], a[1] * c[0] + b[1]] }
+        )
+    }
+
+    /// (`ps_madds1`) Paired-Single Multiply-Add Scalar Low, ``self × c.ps1 + b``.
+    #[inline(always)]
+    pub fn ps_madds1(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(15<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [a[0] * c[1] + b[0], a[1] * c[1] + b[1]] }
+        )
+    }
+
+    /// (`ps_sum0`) Paired-Single Vector Sum High, ``(self.ps0 + b.ps1, c.ps1)``.
+    #[inline(always)]
+    pub fn ps_sum0(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(10<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [a[0] + b[1], c[1]] }
+        )
+    }
+
+    /// (`ps_sum1`) Paired-Single Vector Sum Low, ``(c.ps0, self.ps0 + b.ps1)``.
+    #[inline(always)]
+    pub fn ps_sum1(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(11<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| { [c[0], a[0] + b[1]] }
+        )
+    }
+
+    /// (`ps_sel`) Paired-Single Select, ``c`` in the halves where ``self >= 0``, ``b`` elsewhere.
+    #[inline(always)]
+    pub fn ps_sel(self, c: f32x2, b: f32x2) -> f32x2 {
+        paired!(
+            "({fa}<<16)|({fb}<<11)|({fc}<<6)|(23<<1)",
+            self,
+            b,
+            c,
+            |a, b, c| {
+                let select = |i: usize| if a[i] >= 0.0 { c[i] } else { b[i] };
+                [select(0), select(1)]
+            }
+        )
+    }
+
+    /// (`ps_res`) Paired-Single Reciprocal Estimate, ``1 / self``.
+    ///
+    /// The estimate is only accurate to 1/4096 on Broadway.
+    #[inline(always)]
+    pub fn ps_res(self) -> f32x2 {
+        paired!("({fb}<<11)|(24<<1)", self, self, self, |_, b, _| {
+            [1.0 / b[0], 1.0 / b[1]]
+        })
+    }
+
+    /// (`ps_rsqrte`) Paired-Single Reciprocal Square Root Estimate, ``1 / √self``.
+    ///
+    /// The estimate is only accurate to 1/4096 on Broadway.
+    #[inline(always)]
+    pub fn ps_rsqrte(self) -> f32x2 {
+        paired!("({fb}<<11)|(26<<1)", self, self, self, |_, b, _| {
+            [rsqrt(b[0]), rsqrt(b[1])]
+        })
+    }
+
+    /// (`ps_neg`) Paired-Single Negate, ``-self``.
+    #[inline(always)]
+    pub fn ps_neg(self) -> f32x2 {
+        paired!("({fb}<<11)|(40<<1)", self, self, self, |_, b, _| [
+            -b[0], -b[1]
+        ])
+    }
+
+    /// (`ps_abs`) Paired-Single Absolute Value, ``|self|``.
+    #[inline(always)]
+    pub fn ps_abs(self) -> f32x2 {
+        paired!("({fb}<<11)|(264<<1)", self, self, self, |_, b, _| {
+            let abs = |x: f32| f32::from_bits(x.to_bits() & 0x7fff_ffff);
+            [abs(b[0]), abs(b[1])]
+        })
+    }
+
+    /// (`ps_nabs`) Paired-Single Negative Absolute Value, ``-|self|``.
+    #[inline(always)]
+    pub fn ps_nabs(self) -> f32x2 {
+        paired!("({fb}<<11)|(136<<1)", self, self, self, |_, b, _| {
+            let nabs = |x: f32| f32::from_bits(x.to_bits() | 0x8000_0000);
+            [nabs(b[0]), nabs(b[1])]
+        })
+    }
+
+    /// (`ps_merge00`) Paired-Single Merge High, ``(self.ps0, b.ps0)``.
+    #[inline(always)]
+    pub fn ps_merge00(self, b: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fb}<<11)|(528<<1)", self, b, b, |a, b, _| {
+            [a[0], b[0]]
+        })
+    }
+
+    /// (`ps_merge01`) Paired-Single Merge Direct, ``(self.ps0, b.ps1)``.
+    #[inline(always)]
+    pub fn ps_merge01(self, b: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fb}<<11)|(560<<1)", self, b, b, |a, b, _| {
+            [a[0], b[1]]
+        })
+    }
+
+    /// (`ps_merge10`) Paired-Single Merge Swapped, ``(self.ps1, b.ps0)``.
+    #[inline(always)]
+    pub fn ps_merge10(self, b: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fb}<<11)|(592<<1)", self, b, b, |a, b, _| {
+            [a[1], b[0]]
+        })
+    }
+
+    /// (`ps_merge11`) Paired-Single Merge Low, ``(self.ps1, b.ps1)``.
+    #[inline(always)]
+    pub fn ps_merge11(self, b: f32x2) -> f32x2 {
+        paired!("({fa}<<16)|({fb}<<11)|(624<<1)", self, b, b, |a, b, _| {
+            [a[1], b[1]]
+        })
+    }
+
+    /// Swap both halves, ``(self.ps1, self.ps0)``.
+    #[inline(always)]
+    pub fn swap(self) -> f32x2 {
+        self.ps_merge10(self)
+    }
+
+    /// Add both halves together, ``self.ps0 + self.ps1``.
+    #[inline(always)]
+    pub fn sum(self) -> f32 {
+        self.ps_sum0(self, self).ps0()
+    }
+
+    /// Dot product of the pairs, ``self.ps0 × b.ps0 + self.ps1 × b.ps1``.
+    #[inline(always)]
+    pub fn dot(self, b: f32x2) -> f32 {
+        self.ps_mul(b).sum()
+    }
+}
+
+impl From<[f32; 2]> for f32x2 {
+    fn from(array: [f32; 2]) -> f32x2 {
+        f32x2(array)
+    }
+}
+
+impl From<f32x2> for [f32; 2] {
+    fn from(pair: f32x2) -> [f32; 2] {
+        pair.0
+    }
+}
+
+impl Add for f32x2 {
+    type Output = f32x2;
+
+    fn add(self, rhs: f32x2) -> f32x2 {
+        self.ps_add(rhs)
+    }
+}
+
+impl Sub for f32x2 {
+    type Output = f32x2;
+
+    fn sub(self, rhs: f32x2) -> f32x2 {
+        self.ps_sub(rhs)
+    }
+}
+
+impl Mul for f32x2 {
+    type Output = f32x2;
+
+    fn mul(self, rhs: f32x2) -> f32x2 {
+        self.ps_mul(rhs)
+    }
+}
+
+impl Mul<f32> for f32x2 {
+    type Output = f32x2;
+
+    fn mul(self, rhs: f32) -> f32x2 {
+        self.ps_muls0(f32x2::splat(rhs))
+    }
+}
+
+impl Div for f32x2 {
+    type Output = f32x2;
+
+    fn div(self, rhs: f32x2) -> f32x2 {
+        self.ps_div(rhs)
+    }
+}
+
+impl Neg for f32x2 {
+    type Output = f32x2;
+
+    fn neg(self) -> f32x2 {
+        self.ps_neg()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: f32x2 = f32x2::new(1.0, 2.0);
+    const B: f32x2 = f32x2::new(10.0, 20.0);
+    const C: f32x2 = f32x2::new(3.0, 4.0);
+
+    #[test]
+    fn sums() {
+        assert_eq!(A.ps_sum0(C, B), f32x2::new(21.0, 4.0));
+        assert_eq!(A.ps_sum1(C, B), f32x2::new(3.0, 21.0));
+        assert_eq!(f32x2::new(1.5, 2.5).sum(), 4.0);
+        assert_eq!(A.dot(C), 11.0);
+    }
+
+    #[test]
+    fn multiply_add_scalar() {
+        assert_eq!(A.ps_madds0(C, B), f32x2::new(13.0, 26.0));
+        assert_eq!(A.ps_madds1(C, B), f32x2::new(14.0, 28.0));
+        assert_eq!(A.ps_muls0(C), f32x2::new(3.0, 6.0));
+        assert_eq!(A.ps_muls1(C), f32x2::new(4.0, 8.0));
+    }
+
+    #[test]
+    fn select() {
+        assert_eq!(f32x2::new(0.0, -1.0).ps_sel(C, B), f32x2::new(3.0, 20.0));
+        assert_eq!(f32x2::new(-0.0, 1.0).ps_sel(C, B), f32x2::new(3.0, 4.0));
+        assert_eq!(
+            f32x2::new(f32::NAN, -f32::NAN).ps_sel(C, B),
+            f32x2::new(10.0, 20.0)
+        );
+    }
+
+    #[test]
+    fn merges() {
+        assert_eq!(A.ps_merge00(B), f32x2::new(1.0, 10.0));
+        assert_eq!(A.ps_merge01(B), f32x2::new(1.0, 20.0));
+        assert_eq!(A.ps_merge10(B), f32x2::new(2.0, 10.0));
+        assert_eq!(A.ps_merge11(B), f32x2::new(2.0, 20.0));
+        assert_eq!(A.swap(), f32x2::new(2.0, 1.0));
+    }
+
+    #[test]
+    fn reciprocal_square_root() {
+        let estimate = f32x2::new(4.0, 0.25).ps_rsqrte();
+        assert!((estimate.ps0() - 0.5).abs() < 0.5 / 4096.0);
+        assert!((estimate.ps1() - 2.0).abs() < 2.0 / 4096.0);
+
+        let special = f32x2::new(0.0, -0.0).ps_rsqrte();
+        assert_eq!(special, f32x2::new(f32::INFINITY, f32::NEG_INFINITY));
+        let special = f32x2::new(f32::INFINITY, -1.0).ps_rsqrte();
+        assert_eq!(special.ps0(), 0.0);
+        assert!(special.ps1().is_nan());
+    }
+}
@@ -0,0 +1,192 @@
+//! ``pi`` module of ``luma_core``.
+//!
+//! Contains the processor interface interrupt controller.
+//!
+//! Every device interrupt reaches Broadway as the external interrupt exception, the processor
+//! interface tells which sources are pending and allows masking each of them.  Pending sources
+//! are dispatched to the handler registered for them, with interrupts disabled; it is up to the
+//! handler to acknowledge the interrupt at its device.
+
+use crate::exception::{Context, Exception};
+use crate::io::{read32, write32};
+use crate::processor;
+use core::cell::UnsafeCell;
+
+const BASE: u32 = 0xcc00_3000;
+
+/// Interrupt cause register.
+const INTSR: u32 = BASE;
+
+/// Interrupt mask register.
+const INTMR: u32 = BASE + 0x04;
+
+/// Reset code register.
+const RESET: u32 = BASE + 0x24;
+
+/// An interrupt source of the processor interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interrupt {
+    /// Processor interface error.
+    Error,
+    /// Reset switch pressed.
+    ResetSwitch,
+    /// Disc interface.
+    Di,
+    /// Serial interface, the controller ports.
+    Si,
+    /// External interface, memory cards and other EXI devices.
+    Exi,
+    /// Audio interface streaming sample counter.
+    Ai,
+    /// DSP, also cascading ARAM and audio DMA completion.
+    Dsp,
+    /// Memory interface.
+    Mem,
+    /// Video interface display interrupts.
+    Vi,
+    /// Pixel engine token.
+    PeToken,
+    /// Pixel engine draw done.
+    PeFinish,
+    /// Command processor FIFO.
+    Cp,
+    /// External debugger.
+    Debug,
+    /// High speed port.
+    Hsp,
+    /// Hollywood, cascading IPC and the other Starlet-side interrupts.
+    Hollywood,
+}
+
+impl Interrupt {
+    /// Every interrupt source, in cause register order.
+    pub const ALL: [Interrupt; 15] = [
+        Interrupt::Error,
+        Interrupt::ResetSwitch,
+        Interrupt::Di,
+        Interrupt::Si,
+        Interrupt::Exi,
+        Interrupt::Ai,
+        Interrupt::Dsp,
+        Interrupt::Mem,
+        Interrupt::Vi,
+        Interrupt::PeToken,
+        Interrupt::PeFinish,
+        Interrupt::Cp,
+        Interrupt::Debug,
+        Interrupt::Hsp,
+        Interrupt::Hollywood,
+    ];
+
+    fn index(self) -> usize {
+        Interrupt::ALL
+            .iter()
+            .position(|&interrupt| interrupt == self)
+            .unwrap()
+    }
+
+    /// Get the bit of this source in the cause and mask registers.
+    pub fn bit(self) -> u32 {
+        1 << self.index()
+    }
+}
+
+/// A Rust interrupt handler, called with the pending source.
+pub type Handler = fn(Interrupt);
+
+/// The registered handlers, only ever accessed with interrupts disabled.
+struct HandlerTable(UnsafeCell<[Option<Handler>; Interrupt::ALL.len()]>);
+
+unsafe impl Sync for HandlerTable {}
+
+impl HandlerTable {
+    fn with<R>(&self, f: impl FnOnce(&mut [Option<Handler>; Interrupt::ALL.len()]) -> R) -> R {
+        processor::critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+}
+
+static HANDLERS: HandlerTable = HandlerTable(UnsafeCell::new([None; Interrupt::ALL.len()]));
+
+/// Mask every source and clear the pending reset switch and error interrupts.
+///
+/// Called by the runtime before interrupts get enabled.
+pub fn init() {
+    write32(INTMR, 0);
+    write32(INTSR, Interrupt::Error.bit() | Interrupt::ResetSwitch.bit());
+}
+
+/// Register a handler for the given source and unmask it, returning the previous handler.
+pub fn register(interrupt: Interrupt, handler: Handler) -> Option<Handler> {
+    HANDLERS.with(|handlers| {
+        let previous = handlers[interrupt.index()].replace(handler);
+        unmask(interrupt);
+        previous
+    })
+}
+
+/// Mask the given source and unregister its handler.
+pub fn unregister(interrupt: Interrupt) -> Option<Handler> {
+    HANDLERS.with(|handlers| {
+        mask(interrupt);
+        handlers[interrupt.index()].take()
+    })
+}
+
+/// Stop the given source from raising the external interrupt.
+pub fn mask(interrupt: Interrupt) {
+    processor::critical_section(|| write32(INTMR, read32(INTMR) & !interrupt.bit()));
+}
+
+/// Allow the given source to raise the external interrupt.
+pub fn unmask(interrupt: Interrupt) {
+    processor::critical_section(|| write32(INTMR, read32(INTMR) | interrupt.bit()));
+}
+
+/// Whether the given source is currently allowed to raise the external interrupt.
+pub fn is_unmasked(interrupt: Interrupt) -> bool {
+    read32(INTMR) & interrupt.bit() != 0
+}
+
+/// Whether the given source is currently asserting its interrupt, masked or not.
+pub fn is_pending(interrupt: Interrupt) -> bool {
+    read32(INTSR) & interrupt.bit() != 0
+}
+
+/// Whether the reset switch is currently held down.
+pub fn reset_switch_pressed() -> bool {
+    // INTSR[RSWST] is cleared while the switch is held.
+    read32(INTSR) & 0x0001_0000 == 0
+}
+
+/// Reset the whole console, as the reset switch of a GameCube does.
+///
+/// **NOTE**: On a Wii, this only resets Broadway and its devices, use the ``/dev/stm`` IOS
+/// driver instead.
+pub fn hot_reset() -> ! {
+    write32(RESET, 0);
+    loop {
+        processor::ppc_nop();
+    }
+}
+
+/// Dispatch every pending and unmasked source to its handler.
+///
+/// Sources nothing handles get masked, so that they can't keep the CPU in the exception.
+pub(crate) fn external_handler(_exception: Exception, _context: &mut Context) {
+    let pending = read32(INTSR) & read32(INTMR);
+    for &interrupt in Interrupt::ALL.iter() {
+        if pending & interrupt.bit() == 0 {
+            continue;
+        }
+
+        // The processor interface owns these two, the others are acknowledged at their device.
+        if interrupt == Interrupt::Error || interrupt == Interrupt::ResetSwitch {
+            write32(INTSR, interrupt.bit());
+        }
+
+        match HANDLERS.with(|handlers| handlers[interrupt.index()]) {
+            Some(handler) => handler(interrupt),
+            None => mask(interrupt),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! ``pmc`` module of ``luma_core``.
+//!
+//! Contains functions for the performance monitor counters.
+//!
+//! Broadway has four counters, PMC1 to PMC4, each able to count a different set of events chosen
+//! through MMCR0 and MMCR1.  A ``Monitor`` assigns the requested events to counters which can
+//! count them.
+
+use crate::spr::{self, Spr};
+
+/// MMCR0[DIS], freezes all counters.
+const MMCR0_DIS: u32 = 0x8000_0000;
+
+/// PMC1 to PMC4, in counter order.
+const PMCS: [Spr; 4] = [Spr::Pmc1, Spr::Pmc2, Spr::Pmc3, Spr::Pmc4];
+
+/// An event which can be counted by the performance monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Processor cycles.
+    Cycles,
+    /// Completed instructions, not including folded branches.
+    InstructionsCompleted,
+    /// Dispatched instructions.
+    InstructionsDispatched,
+    /// Completed ``eieio`` instructions.
+    EieioCompleted,
+    /// Accesses which hit in the L2 cache.
+    L2Hits,
+    /// L1 i(nstruction)-cache misses.
+    L1InstructionMisses,
+    /// Instruction TLB misses.
+    ItlbMisses,
+    /// L2 cache misses caused by instruction fetches.
+    L2InstructionMisses,
+    /// Completed load and store instructions.
+    LoadStoresCompleted,
+    /// L1 d(ata)-cache misses.
+    L1DataMisses,
+    /// Data TLB misses.
+    DtlbMisses,
+    /// L2 cache misses caused by data accesses.
+    L2DataMisses,
+    /// Completed floating-point instructions.
+    FpInstructionsCompleted,
+    /// L2 cache castouts.
+    L2Castouts,
+    /// Mispredicted branches.
+    BranchMispredicts,
+}
+
+impl Event {
+    /// Get the selector of this event for the given counter, if that counter can count it.
+    fn selector(self, counter: usize) -> Option<u32> {
+        match (self, counter) {
+            (Event::Cycles, _) => Some(1),
+            (Event::InstructionsCompleted, _) => Some(2),
+            (Event::InstructionsDispatched, _) => Some(4),
+            (Event::EieioCompleted, 0) => Some(5),
+            (Event::L2Hits, 0) => Some(7),
+            (Event::L1InstructionMisses, 1) => Some(5),
+            (Event::ItlbMisses, 1) => Some(6),
+            (Event::L2InstructionMisses, 1) => Some(7),
+            (Event::LoadStoresCompleted, 1) => Some(11),
+            (Event::L1DataMisses, 2) => Some(5),
+            (Event::DtlbMisses, 2) => Some(6),
+            (Event::L2DataMisses, 2) => Some(7),
+            (Event::FpInstructionsCompleted, 2) => Some(11),
+            (Event::L2Castouts, 3) => Some(5),
+            (Event::BranchMispredicts, 3) => Some(8),
+            _ => None,
+        }
+    }
+}
+
+/// A set of events assigned to the four performance monitor counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Monitor {
+    slots: [Option<Event>; 4],
+}
+
+impl Monitor {
+    /// Assign each of the given events to a counter which can count it.
+    ///
+    /// Returns ``None`` if there are more than four events, or if no assignment exists.
+    pub fn new(events: &[Event]) -> Option<Monitor> {
+        let mut slots = [None; 4];
+        if events.len() <= slots.len() && assign(events, &mut slots) {
+            Some(Monitor { slots })
+        } else {
+            None
+        }
+    }
+
+    /// Get the event assigned to each counter, from PMC1 to PMC4.
+    pub fn events(&self) -> [Option<Event>; 4] {
+        self.slots
+    }
+
+    /// Get the selector of the event assigned to the given counter, 0 counting nothing.
+    fn selector(&self, counter: usize) -> u32 {
+        self.slots[counter]
+            .and_then(|event| event.selector(counter))
+            .unwrap_or(0)
+    }
+
+    /// Get MMCR0[PMC1SELECT] and MMCR0[PMC2SELECT], with MMCR0[DIS] cleared.
+    fn mmcr0(&self) -> u32 {
+        (self.selector(0) << 6) | self.selector(1)
+    }
+
+    /// Get MMCR1[PMC3SELECT] and MMCR1[PMC4SELECT].
+    fn mmcr1(&self) -> u32 {
+        (self.selector(2) << 27) | (self.selector(3) << 22)
+    }
+
+    /// Reset the counters to zero and start counting.
+    pub fn start(&self) {
+        // Freeze everything while reprogramming.
+        spr::write(Spr::Mmcr0, MMCR0_DIS);
+
+        // Reset PMC1 to PMC4.
+        for &pmc in PMCS.iter() {
+            spr::write(pmc, 0);
+        }
+
+        spr::write(Spr::Mmcr1, self.mmcr1());
+        spr::write(Spr::Mmcr0, self.mmcr0());
+    }
+
+    /// Freeze the counters, keeping their current values.
+    pub fn stop(&self) {
+        spr::write(Spr::Mmcr0, spr::read(Spr::Mmcr0) | MMCR0_DIS);
+    }
+
+    /// Read the current value of the counters.
+    pub fn read(&self) -> Sample {
+        Sample {
+            slots: self.slots,
+            counts: [
+                spr::read(Spr::Pmc1),
+                spr::read(Spr::Pmc2),
+                spr::read(Spr::Pmc3),
+                spr::read(Spr::Pmc4),
+            ],
+        }
+    }
+
+    /// Count events while running a closure, returning its result and the counter deltas.
+    pub fn measure<R>(&self, f: impl FnOnce() -> R) -> (R, Sample) {
+        self.start();
+        let result = f();
+        self.stop();
+        (result, self.read())
+    }
+}
+
+/// Try every counter for each event in turn, backtracking when an event can't be placed.
+fn assign(events: &[Event], slots: &mut [Option<Event>; 4]) -> bool {
+    let (event, rest) = match events.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+
+    for counter in 0..slots.len() {
+        if slots[counter].is_none() && event.selector(counter).is_some() {
+            slots[counter] = Some(*event);
+            if assign(rest, slots) {
+                return true;
+            }
+            slots[counter] = None;
+        }
+    }
+
+    false
+}
+
+/// The values of the performance monitor counters at a given time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    slots: [Option<Event>; 4],
+    counts: [u32; 4],
+}
+
+impl Sample {
+    /// Get the count of the given event, if it was being monitored.
+    pub fn get(&self, event: Event) -> Option<u32> {
+        self.iter()
+            .find(|&(slot, _)| slot == event)
+            .map(|(_, count)| count)
+    }
+
+    /// Iterate over every monitored event along with its count.
+    pub fn iter(&self) -> impl Iterator<Item = (Event, u32)> + '_ {
+        self.slots
+            .iter()
+            .zip(self.counts.iter())
+            .filter_map(|(slot, &count)| slot.map(|event| (event, count)))
+    }
+}
+
+/// Count the given events while running a closure, returning its result and the counter deltas.
+///
+/// **NOTE**: This function panics if the events can't all be assigned to a counter.
+pub fn measure<R>(events: &[Event], f: impl FnOnce() -> R) -> (R, Sample) {
+    let monitor = Monitor::new(events).expect("Too many events for the performance monitor");
+    monitor.measure(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_first_counters() {
+        let monitor = Monitor::new(&[Event::Cycles, Event::InstructionsCompleted]).unwrap();
+        assert_eq!(
+            monitor.events(),
+            [
+                Some(Event::Cycles),
+                Some(Event::InstructionsCompleted),
+                None,
+                None
+            ]
+        );
+        assert_eq!(monitor.mmcr0(), 0x0000_0042);
+        assert_eq!(monitor.mmcr1(), 0);
+    }
+
+    #[test]
+    fn encode_every_counter() {
+        let monitor = Monitor::new(&[
+            Event::L1DataMisses,
+            Event::BranchMispredicts,
+            Event::L1InstructionMisses,
+            Event::EieioCompleted,
+        ])
+        .unwrap();
+        assert_eq!(
+            monitor.events(),
+            [
+                Some(Event::EieioCompleted),
+                Some(Event::L1InstructionMisses),
+                Some(Event::L1DataMisses),
+                Some(Event::BranchMispredicts),
+            ]
+        );
+        assert_eq!(monitor.mmcr0(), 0x0000_0145);
+        assert_eq!(monitor.mmcr1(), 0x2a00_0000);
+    }
+
+    #[test]
+    fn assign_with_backtracking() {
+        // Cycles first takes PMC1, then moves to PMC2 to make room for the eieio count.
+        let monitor = Monitor::new(&[Event::Cycles, Event::EieioCompleted]).unwrap();
+        assert_eq!(
+            monitor.events(),
+            [Some(Event::EieioCompleted), Some(Event::Cycles), None, None]
+        );
+        assert_eq!(monitor.mmcr0(), 0x0000_0141);
+
+        assert_eq!(Monitor::new(&[Event::L2Hits, Event::EieioCompleted]), None);
+        assert_eq!(Monitor::new(&[Event::Cycles; 5]), None);
+    }
+
+    #[test]
+    fn sample() {
+        let sample = Sample {
+            slots: [None, Some(Event::Cycles), None, Some(Event::L2Castouts)],
+            counts: [1, 2, 3, 4],
+        };
+        assert_eq!(sample.get(Event::Cycles), Some(2));
+        assert_eq!(sample.get(Event::L2Castouts), Some(4));
+        assert_eq!(sample.get(Event::L2Hits), None);
+        assert_eq!(sample.iter().count(), 2);
+    }
+}
@@ -1,17 +1,65 @@
 //! ``processor`` module of ``luma_core``.
 //!
 //! Contains functions for system instructions.
+//!
+//! Other targets have no interrupts to mask nor power states to enter, so the functions for them
+//! do nothing there, and the synchronization instructions become fences.
+
+use core::marker::PhantomData;
+#[cfg(not(target_arch = "powerpc"))]
+use core::sync::atomic::{compiler_fence, fence, Ordering};
 
 /// PowerPC NOP Instruction
 #[inline(always)]
 pub fn ppc_nop() {
-    unsafe { asm!("nop", options(nostack)) }
+    #[cfg(target_arch = "powerpc")]
+    unsafe {
+        asm!("nop", options(nostack))
+    }
 }
 
 /// PowerPC Execution Synchronization
+///
+/// Waits for every preceding instruction, memory accesses included, to complete.
 #[inline(always)]
 pub fn ppc_exec_sync() {
-    unsafe { asm!("sync", options(nostack)) }
+    #[cfg(target_arch = "powerpc")]
+    unsafe {
+        asm!("sync", options(nostack))
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    fence(Ordering::SeqCst);
+}
+
+/// PowerPC Enforce In-Order Execution of I/O
+///
+/// Orders the preceding memory accesses before the following ones, without waiting for them,
+/// which is enough between two hardware register accesses.
+#[inline(always)]
+pub fn ppc_eieio() {
+    #[cfg(target_arch = "powerpc")]
+    unsafe {
+        asm!("eieio", options(nostack))
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    fence(Ordering::SeqCst);
+}
+
+/// PowerPC Instruction Synchronization
+///
+/// Discards the prefetched instructions, so the following ones run in the context set up by
+/// the preceding ones, for instance after writing the MSR or a HID register.
+#[inline(always)]
+pub fn ppc_isync() {
+    #[cfg(target_arch = "powerpc")]
+    unsafe {
+        asm!("isync", options(nostack))
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    compiler_fence(Ordering::SeqCst);
 }
 
 /// PowerPC System Halt
@@ -25,6 +73,7 @@ pub fn ppc_halt() {
         // NOP Instruction.
         ppc_nop();
 
+        #[cfg(target_arch = "powerpc")]
         unsafe {
             // Load Immediate.
             asm!("li 3,0", options(nostack));
@@ -42,51 +91,140 @@ pub fn ppc_halt() {
 #[inline(always)]
 pub fn ppc_ctx_sync() {
     // Context Synchronization.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
         asm!("sc", options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    fence(Ordering::SeqCst);
+}
+
+/// PowerPC CPU Doze
+///
+/// Sets HID0[DOZE] and MSR[POW], stopping instruction execution until the next exception
+/// wakes the CPU up.  Returns immediately if interrupts are disabled, since nothing but a
+/// reset could wake it up then.
+#[inline(always)]
+pub fn ppc_doze() {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register variable.
+        let mut _val = 0u32;
+
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mfmsr {0}",
+                 "andi. {0},{0},0x8000",
+                 "beq 1f",
+                 "mfspr {0},1008",
+                 "rlwinm {0},{0},0,11,8",
+                 "oris {0},{0},0x0080",
+                 "mtspr 1008,{0}",
+                 "sync",
+                 "mfmsr {0}",
+                 "oris {0},{0},0x0004",
+                 "mtmsr {0}",
+                 "isync",
+                 "1:",
+                inout(reg) _val, options(nostack));
+        }
+    }
+}
+
+/// PowerPC CPU Doze Until
+///
+/// Dozes until ``done`` returns true, for instance once an exception handler has updated some
+/// state.  ``done`` runs with interrupts disabled, and MSR[EE] and MSR[POW] are then set by the
+/// same ``mtmsr``, so that an exception can't slip in between the check and the doze and leave
+/// the CPU asleep.  Spins on ``done`` if interrupts are disabled, like ``ppc_doze`` returns.
+#[inline(always)]
+pub fn ppc_doze_until(mut done: impl FnMut() -> bool) {
+    loop {
+        let guard = InterruptGuard::new();
+        if done() {
+            return;
+        }
+        if !guard.were_enabled() {
+            continue;
+        }
+
+        // Run the assembly instruction, the exception waking the CPU up returning after it.
+        #[cfg(target_arch = "powerpc")]
+        unsafe {
+            asm!("mfspr {0},1008",
+                 "rlwinm {0},{0},0,11,8",
+                 "oris {0},{0},0x0080",
+                 "mtspr 1008,{0}",
+                 "sync",
+                 "mfmsr {0}",
+                 "oris {0},{0},0x0004",
+                 "ori {0},{0},0x8000",
+                 "sync",
+                 "mtmsr {0}",
+                 "isync",
+                out(reg) _, options(nostack));
+        }
+    }
 }
 
 /// PowerPC CPU ISR Enable
 #[inline(always)]
 pub fn cpu_isr_enable() {
-    // Define a register variable.
-    let mut _val = 0u32;
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register variable.
+        let mut _val = 0u32;
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("mfmsr {0}",
-             "ori {0},{0},0x8000",
-             "mtmsr {0}",
-            inout(reg) _val, options(nostack));
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mfmsr {0}",
+                 "ori {0},{0},0x8000",
+                 "mtmsr {0}",
+                inout(reg) _val, options(nostack));
+        }
     }
 }
 
 /// PowerPC CPU ISR Disable
+///
+/// Clears MSR[EE] and returns the previous state of the bit as a cookie,
+/// which should be handed back to ``cpu_isr_restore``.
 #[inline(always)]
-pub fn cpu_isr_disable(mut _isr_cookie: u32) {
-    // Define variables.
-    let mut _disable_mask = 0u32;
-    _isr_cookie = 0;
+pub fn cpu_isr_disable() -> u32 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define variables.
+        let mut isr_cookie;
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("mfmsr {0}",
-             "rlwinm {1},{0},0,17,15",
-             "mtmsr {1}",
-             "extrwi {0},{0},1,16",
-            inout(reg) _isr_cookie, inout(reg) _disable_mask, 
-            options(nostack));
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mfmsr {0}",
+                 "rlwinm {1},{0},0,17,15",
+                 "mtmsr {1}",
+                 "extrwi {0},{0},1,16",
+                out(reg) isr_cookie, out(reg) _,
+                options(nostack));
+        }
+
+        // Return the previous MSR[EE] state.
+        isr_cookie
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        // Other targets have no interrupts to disable.
+        0
     }
 }
 
 /// PowerPC CPU ISR Restore
+///
+/// Sets MSR[EE] again if the cookie returned by ``cpu_isr_disable`` says it was set.
 #[inline(always)]
-pub fn cpu_isr_restore(mut _isr_cookie: u32) {
-    // Define mask variable.
-    let mut _enable_mask = 0u32;
-
+pub fn cpu_isr_restore(isr_cookie: u32) {
     // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
         asm!("cmpwi {0},0",
              "beq 1f",
@@ -94,7 +232,55 @@ pub fn cpu_isr_restore(mut _isr_cookie: u32) {
              "ori {1},{1},0x8000",
              "mtmsr {1}",
              "1:",
-            inout(reg) _isr_cookie, inout(reg) _enable_mask,
+            in(reg) isr_cookie, out(reg) _,
             options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    let _ = isr_cookie;
+}
+
+/// A guard which keeps interrupts disabled for as long as it is alive.
+///
+/// The previous MSR[EE] state is restored when the guard is dropped, so guards can be nested.
+pub struct InterruptGuard {
+    isr_cookie: u32,
+    // Interrupt state belongs to the CPU, the guard must not move to another thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl InterruptGuard {
+    /// Disable interrupts until the returned guard is dropped.
+    #[inline(always)]
+    pub fn new() -> InterruptGuard {
+        InterruptGuard {
+            isr_cookie: cpu_isr_disable(),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Whether interrupts were enabled when this guard got created.
+    pub fn were_enabled(&self) -> bool {
+        self.isr_cookie != 0
+    }
+}
+
+impl Default for InterruptGuard {
+    fn default() -> InterruptGuard {
+        InterruptGuard::new()
+    }
+}
+
+impl Drop for InterruptGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        cpu_isr_restore(self.isr_cookie);
+    }
+}
+
+/// Run a closure with interrupts disabled, restoring the previous MSR[EE] state afterwards.
+#[inline(always)]
+pub fn critical_section<R>(f: impl FnOnce() -> R) -> R {
+    let _guard = InterruptGuard::new();
+    f()
 }
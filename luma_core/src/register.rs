@@ -1,8 +1,11 @@
 //! ``register`` module of ``luma_core``.
 //!
 //! Contains functions for register instructions.
+//!
+//! Other targets have none of these registers, reads return zero and writes are ignored.
 
 /// (`mfspr`) PowerPC Register Instruction
+#[cfg(target_arch = "powerpc")]
 #[macro_export]
 macro_rules! mfspr {
     ($R:tt) => {
@@ -16,7 +19,17 @@ macro_rules! mfspr {
     }
 }
 
+/// (`mfspr`) PowerPC Register Instruction, reading zero on other targets.
+#[cfg(not(target_arch = "powerpc"))]
+#[macro_export]
+macro_rules! mfspr {
+    ($R:tt) => {
+        0u32
+    };
+}
+
 /// (`mtspr`) PowerPC Register Instruction
+#[cfg(target_arch = "powerpc")]
 #[macro_export]
 macro_rules! mtspr {
     ($val:expr, $R:tt) => {
@@ -28,58 +41,172 @@ macro_rules! mtspr {
     }
 }
 
+/// (`mtspr`) PowerPC Register Instruction, ignored on other targets.
+#[cfg(not(target_arch = "powerpc"))]
+#[macro_export]
+macro_rules! mtspr {
+    ($val:expr, $R:tt) => {{
+        let _: u32 = $val;
+    }};
+}
+
 /// (`mfpvr`) PowerPC Register Instruction
 #[inline(always)]
 pub fn mfpvr() -> u32 {
-    // Define a register output variable.
-    let mut register;
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("mfpvr {0}",
-            out(reg) register,
-            options(nostack));
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mfpvr {0}",
+                out(reg) register,
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
     }
 
-    // Return the register value.
-    register
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        0
+    }
 }
 
 /// (`mfmsr`) PowerPC Register Instruction
 #[inline(always)]
 pub fn mfmsr() -> u32 {
-    // Define a register output variable.
-    let mut register;
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
 
-    // Run the assembly instruction.
-    unsafe {
-        asm!("mfmsr {0}",
-            out(reg) register,
-            options(nostack));
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mfmsr {0}",
+                out(reg) register,
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
     }
 
-    // Return the register value.
-    register
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        0
+    }
 }
 
 /// (`mtmsr`) PowerPC Register Instruction
 #[inline(always)]
 pub fn mtmsr(value: u32) {
     // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
         asm!("mtmsr {0}",
             in(reg) value,
             options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    let _ = value;
 }
 
 /// (`mtdec`) PowerPC Register Instruction
 #[inline(always)]
 pub fn mtdec(value: u32) {
     // Run the assembly instruction.
+    #[cfg(target_arch = "powerpc")]
     unsafe {
         asm!("mtdec {0}",
             in(reg) value,
             options(nostack));
     }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    let _ = value;
+}
+
+/// (`mftb`) PowerPC Register Instruction
+///
+/// Reads the lower 32 bits of the time base.
+#[inline(always)]
+pub fn mftb() -> u32 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mftb {0}",
+                out(reg) register,
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        0
+    }
+}
+
+/// (`mftbu`) PowerPC Register Instruction
+///
+/// Reads the upper 32 bits of the time base.
+#[inline(always)]
+pub fn mftbu() -> u32 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mftbu {0}",
+                out(reg) register,
+                options(nostack));
+        }
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        0
+    }
+}
+
+/// (`mr`) PowerPC Register Instruction
+///
+/// Reads the stack pointer, ``r1``, which points to the back chain of the current frame.
+#[inline(always)]
+pub fn stack_pointer() -> u32 {
+    #[cfg(target_arch = "powerpc")]
+    {
+        // Define a register output variable.
+        let mut register;
+
+        // Run the assembly instruction.
+        unsafe {
+            asm!("mr {0},1",
+                out(reg) register,
+                options(nomem, nostack));
+        }
+
+        // Return the register value.
+        register
+    }
+
+    #[cfg(not(target_arch = "powerpc"))]
+    {
+        0
+    }
 }
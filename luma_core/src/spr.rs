@@ -0,0 +1,366 @@
+//! ``spr`` module of ``luma_core``.
+//!
+//! Contains the catalogue of the Broadway special-purpose registers, and typed flags for the
+//! hardware implementation dependent registers and the L2 cache control register.
+//!
+//! **NOTE**: ``mfspr`` and ``mtspr`` encode the register number in the instruction itself, so
+//! ``read`` and ``write`` match on the register and are meant to be called with a constant one,
+//! which the compiler reduces to a single instruction.
+
+use crate::{mfspr, mtspr};
+
+/// Define the ``Spr`` catalogue along with ``read`` and ``write``.
+macro_rules! sprs {
+    ($($(#[$doc:meta])* $name:ident = $number:tt,)*) => {
+        /// A Broadway special-purpose register, by its SPR number.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u16)]
+        pub enum Spr {
+            $($(#[$doc])* $name = $number,)*
+        }
+
+        /// Read the given special-purpose register.
+        #[inline(always)]
+        pub fn read(spr: Spr) -> u32 {
+            match spr {
+                $(Spr::$name => mfspr!($number),)*
+            }
+        }
+
+        /// Write the given special-purpose register.
+        ///
+        /// **NOTE**: This doesn't synchronize the context, the HID registers for instance need an
+        /// ``isync`` before the change is guaranteed to apply.
+        #[inline(always)]
+        pub fn write(spr: Spr, value: u32) {
+            match spr {
+                $(Spr::$name => mtspr!(value, $number),)*
+            }
+        }
+    };
+}
+
+sprs! {
+    /// Data storage interrupt status register.
+    Dsisr = 18,
+    /// Data address register.
+    Dar = 19,
+    /// Decrementer.
+    Dec = 22,
+    /// Machine status save/restore register 0.
+    Srr0 = 26,
+    /// Machine status save/restore register 1.
+    Srr1 = 27,
+    /// Operating system scratch register 0.
+    Sprg0 = 272,
+    /// Operating system scratch register 1.
+    Sprg1 = 273,
+    /// Operating system scratch register 2.
+    Sprg2 = 274,
+    /// Operating system scratch register 3.
+    Sprg3 = 275,
+    /// Instruction BAT 0, upper half.
+    Ibat0U = 528,
+    /// Instruction BAT 0, lower half.
+    Ibat0L = 529,
+    /// Instruction BAT 1, upper half.
+    Ibat1U = 530,
+    /// Instruction BAT 1, lower half.
+    Ibat1L = 531,
+    /// Instruction BAT 2, upper half.
+    Ibat2U = 532,
+    /// Instruction BAT 2, lower half.
+    Ibat2L = 533,
+    /// Instruction BAT 3, upper half.
+    Ibat3U = 534,
+    /// Instruction BAT 3, lower half.
+    Ibat3L = 535,
+    /// Data BAT 0, upper half.
+    Dbat0U = 536,
+    /// Data BAT 0, lower half.
+    Dbat0L = 537,
+    /// Data BAT 1, upper half.
+    Dbat1U = 538,
+    /// Data BAT 1, lower half.
+    Dbat1L = 539,
+    /// Data BAT 2, upper half.
+    Dbat2U = 540,
+    /// Data BAT 2, lower half.
+    Dbat2L = 541,
+    /// Data BAT 3, upper half.
+    Dbat3U = 542,
+    /// Data BAT 3, lower half.
+    Dbat3L = 543,
+    /// Instruction BAT 4, upper half.
+    Ibat4U = 560,
+    /// Instruction BAT 4, lower half.
+    Ibat4L = 561,
+    /// Instruction BAT 5, upper half.
+    Ibat5U = 562,
+    /// Instruction BAT 5, lower half.
+    Ibat5L = 563,
+    /// Instruction BAT 6, upper half.
+    Ibat6U = 564,
+    /// Instruction BAT 6, lower half.
+    Ibat6L = 565,
+    /// Instruction BAT 7, upper half.
+    Ibat7U = 566,
+    /// Instruction BAT 7, lower half.
+    Ibat7L = 567,
+    /// Data BAT 4, upper half.
+    Dbat4U = 568,
+    /// Data BAT 4, lower half.
+    Dbat4L = 569,
+    /// Data BAT 5, upper half.
+    Dbat5U = 570,
+    /// Data BAT 5, lower half.
+    Dbat5L = 571,
+    /// Data BAT 6, upper half.
+    Dbat6U = 572,
+    /// Data BAT 6, lower half.
+    Dbat6L = 573,
+    /// Data BAT 7, upper half.
+    Dbat7U = 574,
+    /// Data BAT 7, lower half.
+    Dbat7L = 575,
+    /// Graphics quantization register 0, which has to stay unquantized.
+    Gqr0 = 912,
+    /// Graphics quantization register 1.
+    Gqr1 = 913,
+    /// Graphics quantization register 2.
+    Gqr2 = 914,
+    /// Graphics quantization register 3.
+    Gqr3 = 915,
+    /// Graphics quantization register 4.
+    Gqr4 = 916,
+    /// Graphics quantization register 5.
+    Gqr5 = 917,
+    /// Graphics quantization register 6.
+    Gqr6 = 918,
+    /// Graphics quantization register 7.
+    Gqr7 = 919,
+    /// Hardware implementation dependent register 2, see ``Hid2``.
+    Hid2 = 920,
+    /// Write-gather pipe address register.
+    Wpar = 921,
+    /// Locked cache DMA register, upper half.
+    Dmau = 922,
+    /// Locked cache DMA register, lower half, which triggers the transfer.
+    Dmal = 923,
+    /// Monitor mode control register 0.
+    Mmcr0 = 952,
+    /// Performance monitor counter 1.
+    Pmc1 = 953,
+    /// Performance monitor counter 2.
+    Pmc2 = 954,
+    /// Sampled instruction address register.
+    Sia = 955,
+    /// Monitor mode control register 1.
+    Mmcr1 = 956,
+    /// Performance monitor counter 3.
+    Pmc3 = 957,
+    /// Performance monitor counter 4.
+    Pmc4 = 958,
+    /// Hardware implementation dependent register 0, see ``Hid0``.
+    Hid0 = 1008,
+    /// Hardware implementation dependent register 1, the PLL configuration.
+    Hid1 = 1009,
+    /// Instruction address breakpoint register.
+    Iabr = 1010,
+    /// Hardware implementation dependent register 4, see ``Hid4``.
+    Hid4 = 1011,
+    /// Data address breakpoint register.
+    Dabr = 1013,
+    /// L2 cache control register, see ``L2cr``.
+    L2cr = 1017,
+    /// Instruction cache throttling control register.
+    Ictc = 1019,
+    /// Thermal management register 1.
+    Thrm1 = 1020,
+    /// Thermal management register 2.
+    Thrm2 = 1021,
+    /// Thermal management register 3.
+    Thrm3 = 1022,
+}
+
+/// Implement ``read``, ``write`` and ``modify`` for a flags type of a register.
+macro_rules! typed_spr {
+    ($flags:ident, $spr:ident) => {
+        impl $flags {
+            /// Read the register.
+            ///
+            /// **NOTE**: Reserved bits are read as zero.
+            #[inline(always)]
+            pub fn read() -> $flags {
+                $flags::from_bits_truncate(read(Spr::$spr))
+            }
+
+            /// Write the register.
+            #[inline(always)]
+            pub fn write(self) {
+                write(Spr::$spr, self.bits())
+            }
+
+            /// Read the register, change its flags, then write it back.
+            #[inline(always)]
+            pub fn modify(f: impl FnOnce($flags) -> $flags) {
+                f($flags::read()).write()
+            }
+        }
+    };
+}
+
+bitflags::bitflags! {
+    /// HID0, hardware implementation dependent register 0.
+    pub struct Hid0: u32 {
+        /// Enable machine check on bus errors.
+        const EMCP = 0x8000_0000;
+        /// Disable 60x bus address and data parity generation.
+        const DBP = 0x4000_0000;
+        /// Enable 60x bus address parity checking.
+        const EBA = 0x2000_0000;
+        /// Enable 60x bus data parity checking.
+        const EBD = 0x1000_0000;
+        /// CLK_OUT output enable.
+        const BCLK = 0x0800_0000;
+        /// CLK_OUT clock type select.
+        const ECLK = 0x0200_0000;
+        /// Disable precharge of the ARTRY and SHD signals.
+        const PAR = 0x0100_0000;
+        /// Doze mode, entered once MSR[POW] is set.
+        const DOZE = 0x0080_0000;
+        /// Nap mode, entered once MSR[POW] is set.
+        const NAP = 0x0040_0000;
+        /// Sleep mode, entered once MSR[POW] is set.
+        const SLEEP = 0x0020_0000;
+        /// Dynamic power management.
+        const DPM = 0x0010_0000;
+        /// Not hard reset, cleared by a hard reset only.
+        const NHR = 0x0001_0000;
+        /// Instruction cache enable.
+        const ICE = 0x0000_8000;
+        /// Data cache enable.
+        const DCE = 0x0000_4000;
+        /// Instruction cache lock.
+        const ILOCK = 0x0000_2000;
+        /// Data cache lock.
+        const DLOCK = 0x0000_1000;
+        /// Instruction cache flash invalidate.
+        const ICFI = 0x0000_0800;
+        /// Data cache flash invalidate.
+        const DCFI = 0x0000_0400;
+        /// Disable speculative cache accesses.
+        const SPD = 0x0000_0200;
+        /// Enable M bit on the bus for instruction fetches.
+        const IFEM = 0x0000_0100;
+        /// Store gathering enable.
+        const SGE = 0x0000_0080;
+        /// Data cache flush assist.
+        const DCFA = 0x0000_0040;
+        /// Branch target instruction cache enable.
+        const BTIC = 0x0000_0020;
+        /// Address broadcast enable, for the cache operations.
+        const ABE = 0x0000_0008;
+        /// Branch history table enable.
+        const BHT = 0x0000_0004;
+        /// ``dcbt`` and ``dcbtst`` are treated as no-ops.
+        const NOOPTI = 0x0000_0001;
+    }
+}
+
+typed_spr!(Hid0, Hid0);
+
+bitflags::bitflags! {
+    /// HID2, hardware implementation dependent register 2.
+    pub struct Hid2: u32 {
+        /// Enable the paired-single quantized loads and stores.
+        const LSQE = 0x8000_0000;
+        /// Enable the write-gather pipe.
+        const WPE = 0x4000_0000;
+        /// Enable the paired-single instructions.
+        const PSE = 0x2000_0000;
+        /// Enable the locked cache, half of the data cache.
+        const LCE = 0x1000_0000;
+        /// Length of the locked cache DMA queue, read-only.
+        const DMAQL = 0x0f00_0000;
+        /// A ``dcbz_l`` cache hit error occurred.
+        const DCHERR = 0x0080_0000;
+        /// A DMA access to non-cacheable space occurred.
+        const DNCERR = 0x0040_0000;
+        /// A DMA cache miss error occurred.
+        const DCMERR = 0x0020_0000;
+        /// A DMA queue overflow occurred.
+        const DQOERR = 0x0010_0000;
+        /// Raise a machine check on ``dcbz_l`` cache hit errors.
+        const DCHEE = 0x0008_0000;
+        /// Raise a machine check on DMA accesses to non-cacheable space.
+        const DNCEE = 0x0004_0000;
+        /// Raise a machine check on DMA cache miss errors.
+        const DCMEE = 0x0002_0000;
+        /// Raise a machine check on DMA queue overflows.
+        const DQOEE = 0x0001_0000;
+    }
+}
+
+typed_spr!(Hid2, Hid2);
+
+impl Hid2 {
+    /// Get the number of locked cache DMA commands still pending in the queue.
+    pub fn dma_queue_length(self) -> u32 {
+        (self & Hid2::DMAQL).bits() >> 24
+    }
+}
+
+bitflags::bitflags! {
+    /// HID4, hardware implementation dependent register 4.
+    pub struct Hid4: u32 {
+        /// Set when HID4 is implemented, always on Broadway.
+        const H4A = 0x8000_0000;
+        /// L2 fetch mode field, 32 byte fetches when clear.
+        const L2FM = 0x6000_0000;
+        /// L2 fetch mode of 64 bytes.
+        const L2FM_64 = 0x2000_0000;
+        /// L2 fetch mode of 128 bytes.
+        const L2FM_128 = 0x4000_0000;
+        /// Bus pipeline depth field.
+        const BPD = 0x1800_0000;
+        /// Dual 64 byte castout buffers.
+        const BCO = 0x0400_0000;
+        /// Secondary bus enable.
+        const SBE = 0x0200_0000;
+        /// Paired-single store of the first half only when zero.
+        const ST0 = 0x0100_0000;
+        /// Paired-single little-endian exception enable.
+        const LPE = 0x0080_0000;
+        /// Data bus parking.
+        const DBP = 0x0040_0000;
+        /// L2 configured as a 2-deep miss-under-miss cache.
+        const L2MUM = 0x0020_0000;
+        /// L2 castout prior to the L2 invalidate flash.
+        const L2CFI = 0x0010_0000;
+    }
+}
+
+typed_spr!(Hid4, Hid4);
+
+bitflags::bitflags! {
+    /// L2CR, L2 cache control register.
+    pub struct L2cr: u32 {
+        /// L2 cache enable.
+        const L2E = 0x8000_0000;
+        /// L2 double-bit error checking enable.
+        const L2CE = 0x4000_0000;
+        /// L2 data only, instructions aren't cached.
+        const L2DO = 0x0040_0000;
+        /// L2 global invalidate.
+        const L2I = 0x0020_0000;
+        /// L2 write-through.
+        const L2WT = 0x0008_0000;
+        /// L2 test support.
+        const L2TS = 0x0004_0000;
+        /// L2 global invalidate in progress, read-only.
+        const L2IP = 0x0000_0001;
+    }
+}
+
+typed_spr!(L2cr, L2cr);
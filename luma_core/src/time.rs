@@ -0,0 +1,182 @@
+//! ``time`` module of ``luma_core``.
+//!
+//! Contains functions for reading the time base and waiting.
+//!
+//! The time base is a 64-bit counter incremented at a quarter of the bus clock, it is never
+//! reset by luma and doesn't wrap in any practical amount of time.
+
+use crate::register::{mftb, mftbu};
+use crate::{alarm, processor};
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::time::Duration;
+
+/// Bus clock of the Wii, in Hz.
+pub const WII_BUS_CLOCK: u32 = 243_000_000;
+
+/// Bus clock of the GameCube, in Hz.
+pub const GAMECUBE_BUS_CLOCK: u32 = 162_000_000;
+
+/// Bus clock of the console luma is built for, in Hz.
+#[cfg(not(feature = "gamecube"))]
+pub const BUS_CLOCK: u32 = WII_BUS_CLOCK;
+
+/// Bus clock of the console luma is built for, in Hz.
+#[cfg(feature = "gamecube")]
+pub const BUS_CLOCK: u32 = GAMECUBE_BUS_CLOCK;
+
+/// Frequency of the time base and of the decrementer, in Hz.
+pub const TIMEBASE_CLOCK: u32 = BUS_CLOCK / 4;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Read the full 64-bit time base.
+///
+/// The upper half is read again after the lower one, so that a carry between both reads can't
+/// produce a torn value.
+#[inline]
+pub fn timebase() -> u64 {
+    loop {
+        let upper = mftbu();
+        let lower = mftb();
+        if mftbu() == upper {
+            return ((upper as u64) << 32) | (lower as u64);
+        }
+    }
+}
+
+/// Convert a number of time base ticks to a duration.
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    let clock = TIMEBASE_CLOCK as u64;
+    let secs = ticks / clock;
+    let nanos = (ticks % clock) * NANOS_PER_SEC / clock;
+    Duration::new(secs, nanos as u32)
+}
+
+/// Convert a duration to a number of time base ticks, rounding up.
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let clock = TIMEBASE_CLOCK as u64;
+    let nanos = duration.subsec_nanos() as u64;
+    duration
+        .as_secs()
+        .saturating_mul(clock)
+        .saturating_add((nanos * clock + NANOS_PER_SEC - 1) / NANOS_PER_SEC)
+}
+
+/// A point in time, as read from the time base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// Get the current time.
+    pub fn now() -> Instant {
+        Instant(timebase())
+    }
+
+    /// Create an instant from a raw time base value.
+    pub fn from_ticks(ticks: u64) -> Instant {
+        Instant(ticks)
+    }
+
+    /// Get the raw time base value of this instant.
+    pub fn ticks(&self) -> u64 {
+        self.0
+    }
+
+    /// Get the time elapsed since this instant.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    /// Get the time elapsed from ``earlier`` to this instant, or ``None`` if it is later.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(ticks_to_duration)
+    }
+
+    /// Get the time elapsed from ``earlier`` to this instant, or zero if it is later.
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    /// Get the time elapsed from ``earlier`` to this instant.
+    ///
+    /// **NOTE**: This function panics if ``earlier`` is later than this instant.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier)
+            .expect("Supplied instant is later than self")
+    }
+
+    /// Add a duration to this instant, or ``None`` on overflow.
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration_to_ticks(duration)).map(Instant)
+    }
+
+    /// Subtract a duration from this instant, or ``None`` on underflow.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration_to_ticks(duration)).map(Instant)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .expect("Overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        self.checked_sub(duration)
+            .expect("Overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// Spin until the time base reaches ``deadline``.
+fn spin_until(deadline: u64) {
+    while timebase() < deadline {}
+}
+
+/// Busy-wait for at least the given number of microseconds.
+pub fn udelay(us: u32) {
+    spin_until(timebase() + duration_to_ticks(Duration::from_micros(us as u64)));
+}
+
+/// Busy-wait for at least the given number of milliseconds.
+pub fn mdelay(ms: u32) {
+    spin_until(timebase() + duration_to_ticks(Duration::from_millis(ms as u64)));
+}
+
+/// Sleep for at least the given duration.
+///
+/// The CPU dozes until an alarm wakes it up at the deadline.
+///
+/// **NOTE**: With interrupts disabled, this spins on the time base like ``udelay`` does.
+pub fn sleep(duration: Duration) {
+    let deadline = timebase().saturating_add(duration_to_ticks(duration));
+    let alarm = alarm::set_alarm_at(Instant(deadline), || {});
+    processor::ppc_doze_until(|| timebase() >= deadline);
+    alarm::cancel_alarm(alarm);
+}
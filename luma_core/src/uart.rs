@@ -0,0 +1,68 @@
+//! ``uart`` module of ``luma_core``.
+//!
+//! Contains functions for the debug UART of the IPL device, behind channel 0 of the external
+//! interface.
+//!
+//! Retail consoles have nothing listening there, but Dolphin shows everything written to it in
+//! its log, as ``OSREPORT`` messages.
+
+use crate::io::{read32, write32};
+use crate::processor;
+use core::fmt;
+
+/// Channel 0 of the external interface.
+const BASE: u32 = 0xcc00_6800;
+
+/// Channel parameter register.
+const CSR: u32 = BASE;
+
+/// Channel control register.
+const CR: u32 = BASE + 0x0c;
+
+/// Channel immediate data register.
+const DATA: u32 = BASE + 0x10;
+
+/// CSR bits kept when selecting a device, the interrupt masks.
+const CSR_KEEP: u32 = 0x405;
+
+/// CSR value selecting device 1 at 8 MHz.
+const CSR_SELECT: u32 = 0x130;
+
+/// CR value starting a 4 byte write immediate transfer.
+const CR_WRITE: u32 = 0x35;
+
+/// Command writing to the UART of the IPL device.
+const WRITE_COMMAND: u32 = 0xa001_0000;
+
+/// Write one word to the selected device.
+fn write_word(word: u32) {
+    write32(DATA, word);
+    write32(CR, CR_WRITE);
+    while read32(CR) & 1 != 0 {}
+}
+
+/// Send bytes to the UART, four at a time.
+pub fn write(bytes: &[u8]) {
+    for chunk in bytes.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+
+        processor::critical_section(|| {
+            let csr = read32(CSR) & CSR_KEEP;
+            write32(CSR, csr | CSR_SELECT);
+            write_word(WRITE_COMMAND);
+            write_word(u32::from_be_bytes(word));
+            write32(CSR, csr);
+        });
+    }
+}
+
+/// A ``fmt::Write`` implementation sending the text to the UART.
+pub struct Uart;
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(s.as_bytes());
+        Ok(())
+    }
+}
@@ -3,7 +3,9 @@
 //! Contains functions for basic video access.
 
 use crate::allocate::alloc_aligned;
-use crate::io::{read16, write16, write32};
+use crate::event::{self, Event};
+use crate::io::{read16, read32, write16, write32};
+use crate::pi;
 use alloc::boxed::Box;
 use core::pin::Pin;
 
@@ -218,6 +220,60 @@ unsafe fn setup_interlaced(width: usize, height: usize, xfb: &Xfb) {
     set_border();
 }
 
+/// Retraces of the display, counted by the first display interrupt.
+static RETRACE: Event = Event::new();
+
+/// Acknowledge the pending display interrupts, and signal a retrace on the first one.
+fn interrupt_handler(_interrupt: pi::Interrupt) {
+    for n in 0..4 {
+        let register = BASE + 0x30 + n * 4;
+        let value = read32(register);
+        if value & 0x8000_0000 != 0 {
+            write32(register, value & !0x8000_0000);
+            if n == 0 {
+                RETRACE.signal();
+            }
+        }
+    }
+}
+
+/// Turn the display off and unregister the retrace interrupt, before handing the console over
+/// to another program.
+pub fn shutdown() {
+    pi::unregister(pi::Interrupt::Vi);
+    unsafe { configure(ConfigureFlags::empty()) };
+}
+
+/// Get the address, width and height of the XFB being scanned out, or ``None`` if the display
+/// is off.
+///
+/// The address is the uncached mapping of the XFB, so that drawing to it shows up without any
+/// flush, even when nothing else of the program can be trusted anymore.
+pub fn scanout() -> Option<(*mut u8, usize, usize)> {
+    let flags = ConfigureFlags::from_bits_truncate(read16(BASE + 0x02));
+    let top = read32(BASE + 0x1c);
+    if !flags.contains(ConfigureFlags::ENABLE) || top == 0 {
+        return None;
+    }
+
+    let address = if top & (1 << 28) != 0 {
+        (top & 0x00ff_ffff) << 5
+    } else {
+        top & 0x00ff_ffff
+    };
+    let width = (read16(BASE + 0x48) & 0xff) as usize * 16 / 2;
+    let mut height = ((read16(BASE) >> 4) & 0x3ff) as usize;
+    if !flags.contains(ConfigureFlags::PROGRESSIVE) {
+        height *= 2;
+    }
+
+    Some((
+        (0xc000_0000 | (address & 0x1fff_ffff)) as *mut u8,
+        width,
+        height,
+    ))
+}
+
 /// A struct representing the Video Interface, or VI.  This is the piece of hardware which scans
 /// out the XFB to the screen.
 pub struct Vi {
@@ -228,6 +284,7 @@ impl Vi {
     /// Setup the VI with the given XFB.
     pub fn setup(xfb: Xfb) -> Vi {
         unsafe { setup_interlaced(xfb.width(), xfb.height(), &xfb) };
+        pi::register(pi::Interrupt::Vi, interrupt_handler);
         Vi { xfb }
     }
 
@@ -236,6 +293,16 @@ impl Vi {
         &mut self.xfb
     }
 
+    /// Get a future resolving at the next retrace, to the number of retraces since setup.
+    pub fn next_vsync(&self) -> event::Next<'static> {
+        RETRACE.next()
+    }
+
+    /// Get the number of retraces since setup.
+    pub fn retrace_count(&self) -> u64 {
+        RETRACE.count()
+    }
+
     // TODO: document!
     pub fn visel(&self) -> u16 {
         unsafe { get_visel() }
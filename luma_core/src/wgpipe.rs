@@ -0,0 +1,135 @@
+//! ``wgpipe`` module of ``luma_core``.
+//!
+//! Contains the write-gather pipe, which batches uncached stores into 32 byte bursts.
+//!
+//! Once enabled, every store to the physical address programmed in WPAR is gathered into a
+//! 128 byte buffer instead of going out on the bus on its own, and the buffer is sent as
+//! 32 byte bursts as it fills up.  This is how commands are submitted to the GX FIFO.
+//!
+//! Other targets have no pipe to write to, so only its configuration builds there.
+
+use crate::processor;
+use crate::spr::{self, Hid2, Spr};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Physical address of the GX command FIFO, the usual target of the pipe.
+pub const GX_FIFO: u32 = 0x0c00_8000;
+
+/// WPAR[BNE], the buffer isn't empty yet.
+const WPAR_BNE: u32 = 0x0000_0001;
+
+/// Whether a ``WriteGatherPipe`` currently exists.
+static WRITE_GATHER_PIPE_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// A struct representing the write-gather pipe, gathering the stores to a single physical
+/// address.  Values are written to it in the order the target expects them, for instance a
+/// stream of GX commands.
+///
+/// The pipe is flushed and disabled again once this struct is dropped.
+pub struct WriteGatherPipe {
+    /// Uncached effective address of the target.
+    address: u32,
+}
+
+impl WriteGatherPipe {
+    /// Enable the write-gather pipe, targeting the given physical address.
+    ///
+    /// **NOTE**: This function panics if the pipe is already enabled, or if ``address`` isn't
+    /// aligned on a 32 byte boundary.
+    pub fn enable(address: u32) -> WriteGatherPipe {
+        assert!(
+            address & 0x1f == 0,
+            "The write-gather pipe address must be 32 byte aligned"
+        );
+        if WRITE_GATHER_PIPE_TAKEN.swap(true, Ordering::Acquire) {
+            panic!("The write-gather pipe is already enabled");
+        }
+
+        spr::write(Spr::Wpar, address);
+        Hid2::modify(|hid2| hid2 | Hid2::WPE);
+        processor::ppc_isync();
+
+        WriteGatherPipe {
+            address: 0xc000_0000 | address,
+        }
+    }
+
+    /// Get the physical address the pipe targets.
+    pub fn address(&self) -> u32 {
+        self.address & !0xc000_0000
+    }
+
+    /// Whether the pipe has sent every gathered value.
+    pub fn is_empty(&self) -> bool {
+        spr::read(Spr::Wpar) & WPAR_BNE == 0
+    }
+
+    /// Write a 8-bit value to the pipe.
+    #[cfg(target_arch = "powerpc")]
+    #[inline(always)]
+    pub fn write_u8(&mut self, value: u8) {
+        // Run the assembly instruction.
+        unsafe {
+            asm!("stb {0},0({1})",
+                in(reg) value, in(reg_nonzero) self.address,
+                options(nostack));
+        }
+    }
+
+    /// Write a 16-bit value to the pipe.
+    #[cfg(target_arch = "powerpc")]
+    #[inline(always)]
+    pub fn write_u16(&mut self, value: u16) {
+        // Run the assembly instruction.
+        unsafe {
+            asm!("sth {0},0({1})",
+                in(reg) value, in(reg_nonzero) self.address,
+                options(nostack));
+        }
+    }
+
+    /// Write a 32-bit value to the pipe.
+    #[cfg(target_arch = "powerpc")]
+    #[inline(always)]
+    pub fn write_u32(&mut self, value: u32) {
+        // Run the assembly instruction.
+        unsafe {
+            asm!("stw {0},0({1})",
+                in(reg) value, in(reg_nonzero) self.address,
+                options(nostack));
+        }
+    }
+
+    /// Write a 32-bit floating value to the pipe.
+    #[cfg(target_arch = "powerpc")]
+    #[inline(always)]
+    pub fn write_f32(&mut self, value: f32) {
+        // Run the assembly instruction.
+        unsafe {
+            asm!("stfs {0},0({1})",
+                in(freg) value, in(reg_nonzero) self.address,
+                options(nostack));
+        }
+    }
+
+    /// Push out a partially filled burst by padding it with zeroes, then wait for the pipe to be
+    /// empty.
+    #[cfg(target_arch = "powerpc")]
+    pub fn flush(&mut self) {
+        for _ in 0..8 {
+            self.write_u32(0);
+        }
+        processor::ppc_exec_sync();
+        while !self.is_empty() {}
+    }
+}
+
+impl Drop for WriteGatherPipe {
+    fn drop(&mut self) {
+        #[cfg(target_arch = "powerpc")]
+        self.flush();
+        Hid2::modify(|hid2| hid2 - Hid2::WPE);
+        processor::ppc_isync();
+        WRITE_GATHER_PIPE_TAKEN.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,55 @@
+//! ``diag`` module of ``luma_runtime``.
+//!
+//! Contains the diagnostic output, where errors returned by ``main`` are reported.
+//!
+//! The text goes to the USB Gecko in slot B when one is present, and is discarded otherwise.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+use luma_core::gecko::{self, Gecko};
+
+/// Whether a USB Gecko was found, probed on the first write.
+static GECKO: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+const UNKNOWN: u8 = 0;
+const ABSENT: u8 = 1;
+const PRESENT: u8 = 2;
+
+/// Whether the diagnostic output goes anywhere.
+pub fn is_connected() -> bool {
+    match GECKO.load(Ordering::Relaxed) {
+        UNKNOWN => {
+            let present = gecko::is_present();
+            GECKO.store(if present { PRESENT } else { ABSENT }, Ordering::Relaxed);
+            present
+        }
+        state => state == PRESENT,
+    }
+}
+
+/// Write formatted text to the diagnostic output, used by ``eprint!`` and ``eprintln!``.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments<'_>) {
+    if is_connected() {
+        let _ = Gecko.write_fmt(args);
+    }
+}
+
+/// Print to the diagnostic output.
+#[macro_export]
+macro_rules! eprint {
+    ($($arg:tt)*) => {
+        $crate::diag::_print(format_args!($($arg)*))
+    };
+}
+
+/// Print to the diagnostic output, with a newline.
+#[macro_export]
+macro_rules! eprintln {
+    () => {
+        $crate::eprint!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::eprint!("{}\n", format_args!($($arg)*))
+    };
+}
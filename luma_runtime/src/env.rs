@@ -0,0 +1,113 @@
+//! ``env`` module of ``luma_runtime``.
+//!
+//! Contains the command line arguments passed by the loader.
+//!
+//! Loaders such as the Homebrew Channel fill in the argv block of ``crt0`` with a command line
+//! of null-separated arguments, the first one being the path the program was launched from.
+//! Programs started without arguments get none, not even a path.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::{slice, str};
+
+/// Magic value of a filled-in argv block, ``"_arg"``.
+const ARGV_MAGIC: u32 = 0x5f61_7267;
+
+/// The argv block of ``crt0``, as laid out by the loaders.
+#[repr(C)]
+struct SystemArgv {
+    magic: u32,
+    command_line: *const u8,
+    length: usize,
+    argc: i32,
+    argv: *const *const u8,
+    end_argv: *const *const u8,
+}
+
+extern "C" {
+    static __system_argv: *const SystemArgv;
+}
+
+struct ArgsCell(UnsafeCell<Vec<&'static str>>);
+
+unsafe impl Sync for ArgsCell {}
+
+/// The parsed arguments, written once by ``init`` before any thread starts.
+static ARGS: ArgsCell = ArgsCell(UnsafeCell::new(Vec::new()));
+
+/// Validate the argv block and parse its command line, called by the runtime right after the
+/// allocator is set up.
+///
+/// The command line is copied to the heap first, since the loader may have left it anywhere in
+/// memory, then split on null bytes.  Invalid UTF-8 sequences are replaced with ``U+FFFD``.
+pub(crate) fn init() {
+    let system_argv = unsafe { &*__system_argv };
+    if system_argv.magic != ARGV_MAGIC || system_argv.command_line.is_null() {
+        return;
+    }
+
+    let command_line =
+        unsafe { slice::from_raw_parts(system_argv.command_line, system_argv.length) };
+    let command_line: &'static [u8] = Box::leak(Box::from(command_line));
+
+    // The command line ends with the null byte of the last argument.
+    let end = command_line
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |last| last + 1);
+    if end == 0 {
+        return;
+    }
+
+    let args = unsafe { &mut *ARGS.0.get() };
+    args.extend(command_line[..end].split(|&byte| byte == 0).map(|arg| {
+        match str::from_utf8(arg) {
+            Ok(arg) => arg,
+            Err(_) => Box::leak(String::from_utf8_lossy(arg).into_owned().into_boxed_str()),
+        }
+    }));
+}
+
+/// An iterator over the command line arguments, returned by ``args``.
+#[derive(Debug, Clone)]
+pub struct Args {
+    inner: slice::Iter<'static, &'static str>,
+}
+
+impl Iterator for Args {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<&'static str> {
+        self.inner.next().copied()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Args {
+    fn next_back(&mut self) -> Option<&'static str> {
+        self.inner.next_back().copied()
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+/// Get the command line arguments, the first one being the launch path, for instance
+/// ``sd:/apps/example/boot.dol``.
+pub fn args() -> Args {
+    Args {
+        inner: unsafe { &*ARGS.0.get() }.iter(),
+    }
+}
+
+/// Get the directory the program was launched from, for instance ``sd:/apps/example``, to
+/// find its data files.
+pub fn launch_dir() -> Option<&'static str> {
+    let path = args().next()?;
+    let end = path.rfind('/')?;
+    Some(&path[..end])
+}
@@ -0,0 +1,210 @@
+//! ``executor`` module of ``luma_runtime``.
+//!
+//! Contains a single-threaded executor of futures, woken up by interrupt handlers.
+//!
+//! Tasks only get polled once their waker has been called, for instance by an ``Event`` signalled
+//! from the VI retrace interrupt, or by the decrementer alarm behind ``sleep``.  While no task is
+//! ready the executor parks its thread, so that the CPU dozes in the idle thread until the next
+//! interrupt.
+
+use crate::thread::{self, ThreadId};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use luma_core::alarm::{self, AlarmId};
+use luma_core::processor;
+use luma_core::time::Instant;
+
+/// Identifier of the future passed to ``block_on``.
+const MAIN_TASK: usize = usize::MAX;
+
+/// Tasks whose waker got called, along with the thread to unpark.
+struct ReadyQueue {
+    tasks: UnsafeCell<VecDeque<usize>>,
+    thread: ThreadId,
+}
+
+unsafe impl Sync for ReadyQueue {}
+unsafe impl Send for ReadyQueue {}
+
+impl ReadyQueue {
+    fn push(&self, task: usize) {
+        processor::critical_section(|| {
+            let tasks = unsafe { &mut *self.tasks.get() };
+            if !tasks.contains(&task) {
+                tasks.push_back(task);
+            }
+        });
+        thread::unpark(self.thread);
+    }
+
+    fn pop(&self) -> Option<usize> {
+        processor::critical_section(|| unsafe { &mut *self.tasks.get() }.pop_front())
+    }
+}
+
+struct TaskWaker {
+    task: usize,
+    queue: Arc<ReadyQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.queue.push(self.task);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.push(self.task);
+    }
+}
+
+/// An executor, running its tasks on the thread which created it.
+pub struct Executor {
+    tasks: BTreeMap<usize, Pin<Box<dyn Future<Output = ()>>>>,
+    queue: Arc<ReadyQueue>,
+    next_id: usize,
+}
+
+impl Default for Executor {
+    fn default() -> Executor {
+        Executor::new()
+    }
+}
+
+impl Executor {
+    /// Create an executor without any task.
+    pub fn new() -> Executor {
+        Executor {
+            tasks: BTreeMap::new(),
+            queue: Arc::new(ReadyQueue {
+                tasks: UnsafeCell::new(VecDeque::new()),
+                thread: thread::current(),
+            }),
+            next_id: 0,
+        }
+    }
+
+    fn waker(&self, task: usize) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            task,
+            queue: self.queue.clone(),
+        }))
+    }
+
+    /// Add a task, first polled by the next ``block_on`` or ``run``.
+    pub fn spawn(&mut self, future: impl Future<Output = ()> + 'static) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tasks.insert(id, Box::pin(future));
+        self.queue.push(id);
+    }
+
+    /// Poll a ready task.
+    fn poll_task(&mut self, id: usize) {
+        let waker = self.waker(id);
+        let mut cx = Context::from_waker(&waker);
+        let done = match self.tasks.get_mut(&id) {
+            Some(task) => task.as_mut().poll(&mut cx).is_ready(),
+            None => false,
+        };
+        if done {
+            self.tasks.remove(&id);
+        }
+    }
+
+    /// Run the tasks until ``future`` completes, and return its output.
+    ///
+    /// **NOTE**: This function panics if called from another thread than the one which created
+    /// the executor.
+    pub fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+        assert_eq!(
+            thread::current(),
+            self.queue.thread,
+            "An executor only runs on the thread which created it"
+        );
+
+        let mut future = Box::pin(future);
+        let waker = self.waker(MAIN_TASK);
+        self.queue.push(MAIN_TASK);
+        loop {
+            while let Some(id) = self.queue.pop() {
+                if id != MAIN_TASK {
+                    self.poll_task(id);
+                    continue;
+                }
+                if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker))
+                {
+                    return output;
+                }
+            }
+            thread::park();
+        }
+    }
+
+    /// Run the tasks until all of them complete.
+    pub fn run(&mut self) {
+        while !self.tasks.is_empty() {
+            match self.queue.pop() {
+                Some(id) => self.poll_task(id),
+                None => thread::park(),
+            }
+        }
+    }
+}
+
+/// Run ``future`` to completion on the calling thread, and return its output.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    Executor::new().block_on(future)
+}
+
+/// Future returned by ``sleep``.
+pub struct Sleep {
+    deadline: Instant,
+    alarm: Option<AlarmId>,
+}
+
+/// Get a future completing once ``duration`` has elapsed.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Get a future completing once ``deadline`` is reached.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        alarm: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // The waker may change between polls, so always arm a new alarm.
+        if let Some(alarm) = self.alarm.take() {
+            alarm::cancel_alarm(alarm);
+        }
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let waker = cx.waker().clone();
+        self.alarm = Some(alarm::set_alarm_at(self.deadline, move || {
+            waker.wake_by_ref()
+        }));
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(alarm) = self.alarm.take() {
+            alarm::cancel_alarm(alarm);
+        }
+    }
+}
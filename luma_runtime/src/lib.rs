@@ -4,13 +4,48 @@
 //! This module also includes a crt0 implementation for bootstrapping the program.
 //!
 //! **NOTE**: This is currently in a very experimental state and is subject to change.
-#![no_std]
-#![feature(global_asm, lang_items, llvm_asm, alloc_error_handler)]
+#![cfg_attr(not(test), no_std)]
+#![feature(global_asm, asm, lang_items, llvm_asm, alloc_error_handler)]
 
-use core::{alloc::Layout, panic::PanicInfo};
+extern crate alloc;
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    panic::PanicInfo,
+};
 use linked_list_allocator::LockedHeap;
+use luma_core::bat::BatConfig;
 #[allow(unused_imports)]
 use luma_core::cache::*;
+use luma_core::{exception, pi, processor};
+use process::Termination;
+
+// Preemptive threads
+pub mod thread;
+
+// Blocking synchronisation primitives
+pub mod sync;
+
+// Interrupt-driven async executor
+pub mod executor;
+
+// Bounded message queues
+pub mod mqueue;
+
+// Command line arguments
+pub mod env;
+
+// Program exit, reset and power off
+pub mod process;
+
+// Diagnostic output
+pub mod diag;
+
+// Panic reporting
+pub mod panic;
+
+// Main stack overflow detection
+pub mod stack;
 
 // Import linker symbols for allocator initialization.
 extern "C" {
@@ -19,18 +54,54 @@ extern "C" {
 }
 
 // Global Allocator based on ``linked_list_allocator``.
-#[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+#[cfg_attr(not(test), global_allocator)]
+static ALLOCATOR: Allocator = Allocator(LockedHeap::empty());
+
+/// Heap allocator which masks interrupts while the heap is locked, so that exception handlers
+/// can allocate and free memory without deadlocking on the code they interrupted.
+struct Allocator(LockedHeap);
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        processor::critical_section(|| self.0.alloc(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        processor::critical_section(|| self.0.dealloc(ptr, layout))
+    }
+}
+
+// ``critical-section`` implementation, masking MSR[EE] for the duration of the section.
+struct CriticalSection;
+critical_section::set_impl!(CriticalSection);
+
+unsafe impl critical_section::Impl for CriticalSection {
+    unsafe fn acquire() -> critical_section::RawRestoreState {
+        processor::cpu_isr_disable() != 0
+    }
+
+    unsafe fn release(was_enabled: critical_section::RawRestoreState) {
+        processor::cpu_isr_restore(was_enabled as u32);
+    }
+}
+
+// BAT values programmed by ``ConfigBATS`` before translation is enabled.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static __boot_bats: [[u32; 2]; 16] = BatConfig::BOOT.raw();
 
 // crt0 Implementation
+#[cfg(target_arch = "powerpc")]
 global_asm!(include_str!("../asm/crt0.S"));
+#[cfg(target_arch = "powerpc")]
 global_asm!(include_str!("../asm/runtime.S"));
+#[cfg(target_arch = "powerpc")]
 global_asm!(include_str!("../asm/system.S"));
 
 /// This is the executable start function, which directly follows the entry point.
 #[cfg_attr(not(test), lang = "start")]
 #[cfg(not(test))]
-fn start<T>(user_main: fn(), _argc: isize, _argv: *const *const u8) -> isize
+fn start<T>(user_main: fn() -> T, _argc: isize, _argv: *const *const u8) -> isize
 where
     T: Termination,
 {
@@ -44,29 +115,33 @@ where
     // Setup the allocator before the user_main is called.
     unsafe {
         ALLOCATOR
+            .0
             .lock()
             .init(stack_addr, 24 * 1024 * 1024 - out_size);
     }
 
-    // Jump to user defined main function.
-    let user_main: fn() -> T = unsafe { core::mem::transmute(user_main) };
-    user_main();
-
-    panic!("main() cannot return");
-}
+    // Parse the arguments before anything else allocates over them.
+    env::init();
 
-/// Termination trait required for the start function.
-#[cfg_attr(not(test), lang = "termination")]
-trait Termination {}
+    // Install the exception vectors, then enable interrupts with every source masked.
+    exception::install();
+    pi::init();
+    #[cfg(not(feature = "gamecube"))]
+    luma_core::hollywood::init();
+    processor::cpu_isr_enable();
+    thread::init();
+    stack::init();
 
-/// This implementation does the bare minimum to satisfy the executable start function.
-impl Termination for () {}
+    // Jump to user defined main function, then leave with the exit code it reports.
+    let code = user_main().report();
+    process::exit(code)
+}
 
 /// This function is called on panic.
 #[cfg_attr(not(test), panic_handler)]
 #[no_mangle]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {}
+fn panic(info: &PanicInfo) -> ! {
+    panic::handle(info)
 }
 
 /// This function is called when the allocator produces an error.
@@ -77,5 +152,6 @@ fn alloc_error_handler(_layout: Layout) -> ! {
 
 /// Error handler personality language item (current no-op, to satisfy clippy).
 #[cfg_attr(not(test), lang = "eh_personality")]
+#[cfg(not(test))]
 #[no_mangle]
 extern "C" fn rust_eh_personality() {}
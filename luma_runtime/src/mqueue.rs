@@ -0,0 +1,171 @@
+//! ``mqueue`` module of ``luma_runtime``.
+//!
+//! Contains bounded message queues, between threads or from interrupt handlers to threads.
+//!
+//! Messages are received in the order they were sent, except for jammed ones which go to the
+//! front of the queue.  Every operation comes in a blocking, a non-blocking ``try_`` and a
+//! ``_timeout`` variant; only the non-blocking ones may be called from interrupt handlers.
+
+use crate::thread::WaitQueue;
+use alloc::collections::VecDeque;
+use core::cell::UnsafeCell;
+use core::time::Duration;
+use luma_core::processor;
+
+/// A bounded queue of messages of type ``T``.
+pub struct MessageQueue<T> {
+    messages: UnsafeCell<VecDeque<T>>,
+    capacity: usize,
+    /// Receivers waiting for a message.
+    not_empty: WaitQueue,
+    /// Senders waiting for room.
+    not_full: WaitQueue,
+}
+
+unsafe impl<T: Send> Sync for MessageQueue<T> {}
+unsafe impl<T: Send> Send for MessageQueue<T> {}
+
+impl<T> MessageQueue<T> {
+    /// Create an empty queue, holding at most ``capacity`` messages.
+    ///
+    /// **NOTE**: This function panics if ``capacity`` is zero.
+    pub fn new(capacity: usize) -> MessageQueue<T> {
+        assert!(capacity > 0, "A message queue can't be empty");
+        MessageQueue {
+            messages: UnsafeCell::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            not_empty: WaitQueue::new(),
+            not_full: WaitQueue::new(),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut VecDeque<T>) -> R) -> R {
+        processor::critical_section(|| f(unsafe { &mut *self.messages.get() }))
+    }
+
+    /// Get the maximum number of messages in the queue.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of messages currently in the queue.
+    pub fn len(&self) -> usize {
+        self.with(|messages| messages.len())
+    }
+
+    /// Whether the queue currently holds no message.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the queue currently has no room for another message.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+
+    /// Insert ``message`` once there is room, at the back or at the front of the queue.
+    fn push(&self, message: T, front: bool, timeout: Option<Duration>) -> Result<(), T> {
+        let mut message = Some(message);
+        let mut insert = || unsafe {
+            let messages = &mut *self.messages.get();
+            if messages.len() == self.capacity {
+                return None;
+            }
+            let message = message.take().unwrap();
+            if front {
+                messages.push_front(message);
+            } else {
+                messages.push_back(message);
+            }
+            Some(())
+        };
+
+        let sent = match timeout {
+            Some(timeout) if timeout == Duration::from_secs(0) => {
+                processor::critical_section(insert).is_some()
+            }
+            Some(timeout) => self
+                .not_full
+                .wait_until_timeout(timeout, &mut insert)
+                .is_some(),
+            None => {
+                self.not_full.wait_until(&mut insert);
+                true
+            }
+        };
+
+        if sent {
+            self.not_empty.notify_one();
+            Ok(())
+        } else {
+            Err(message.take().unwrap())
+        }
+    }
+
+    /// Remove the message at the front of the queue once there is one.
+    fn pop(&self, timeout: Option<Duration>) -> Option<T> {
+        let remove = || unsafe { (*self.messages.get()).pop_front() };
+        let message = match timeout {
+            Some(timeout) if timeout == Duration::from_secs(0) => {
+                processor::critical_section(remove)
+            }
+            Some(timeout) => self.not_empty.wait_until_timeout(timeout, remove),
+            None => Some(self.not_empty.wait_until(remove)),
+        };
+        if message.is_some() {
+            self.not_full.notify_one();
+        }
+        message
+    }
+
+    /// Send ``message`` to the back of the queue, blocking while it is full.
+    pub fn send(&self, message: T) {
+        let _ = self.push(message, false, None);
+    }
+
+    /// Send ``message`` to the back of the queue, or give it back if the queue is full.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn try_send(&self, message: T) -> Result<(), T> {
+        self.push(message, false, Some(Duration::from_secs(0)))
+    }
+
+    /// Send ``message`` to the back of the queue, or give it back if the queue stayed full for
+    /// ``timeout``.
+    pub fn send_timeout(&self, message: T, timeout: Duration) -> Result<(), T> {
+        self.push(message, false, Some(timeout))
+    }
+
+    /// Send ``message`` to the front of the queue, blocking while it is full.
+    pub fn jam(&self, message: T) {
+        let _ = self.push(message, true, None);
+    }
+
+    /// Send ``message`` to the front of the queue, or give it back if the queue is full.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn try_jam(&self, message: T) -> Result<(), T> {
+        self.push(message, true, Some(Duration::from_secs(0)))
+    }
+
+    /// Send ``message`` to the front of the queue, or give it back if the queue stayed full for
+    /// ``timeout``.
+    pub fn jam_timeout(&self, message: T, timeout: Duration) -> Result<(), T> {
+        self.push(message, true, Some(timeout))
+    }
+
+    /// Receive the message at the front of the queue, blocking while it is empty.
+    pub fn receive(&self) -> T {
+        self.pop(None).unwrap()
+    }
+
+    /// Receive the message at the front of the queue, if there is one.
+    pub fn try_receive(&self) -> Option<T> {
+        self.pop(Some(Duration::from_secs(0)))
+    }
+
+    /// Receive the message at the front of the queue, unless it stayed empty for ``timeout``.
+    pub fn receive_timeout(&self, timeout: Duration) -> Option<T> {
+        self.pop(Some(timeout))
+    }
+}
@@ -0,0 +1,318 @@
+//! ``panic`` module of ``luma_runtime``.
+//!
+//! Contains the panic handler, and the ways to configure it.
+//!
+//! A panic is reported with its message, its location, the usage of the main stack and a stack
+//! trace walked through the back chain, to every output enabled in the ``Config``: the USB
+//! Gecko, the Dolphin log and a red screen drawn over the XFB being scanned out.  The hook set
+//! with ``set_hook`` then runs, for instance to write a crash log to the SD card, before the
+//! processor halts.
+
+use crate::stack;
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use luma_core::gecko::Gecko;
+use luma_core::uart::Uart;
+use luma_core::{font, processor, register, vi};
+
+/// The outputs a panic gets reported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Draw the report over the XFB being scanned out, if the display is on.
+    pub screen: bool,
+    /// Send the report to the USB Gecko, if one is present.
+    pub gecko: bool,
+    /// Send the report to the debug UART, which Dolphin shows in its log.
+    pub dolphin: bool,
+    /// Walk the stack and include a trace in the report.
+    pub backtrace: bool,
+}
+
+impl Config {
+    /// The default configuration, reporting everywhere.
+    pub const DEFAULT: Config = Config {
+        screen: true,
+        gecko: true,
+        dolphin: true,
+        backtrace: true,
+    };
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::DEFAULT
+    }
+}
+
+/// A function called once a panic has been reported.
+pub type Hook = fn(&PanicInfo<'_>, &Backtrace);
+
+struct StateCell(UnsafeCell<(Config, Option<Hook>)>);
+
+unsafe impl Sync for StateCell {}
+
+impl StateCell {
+    fn with<R>(&self, f: impl FnOnce(&mut (Config, Option<Hook>)) -> R) -> R {
+        processor::critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+}
+
+/// The configuration and the hook, only ever accessed with interrupts disabled.
+static STATE: StateCell = StateCell(UnsafeCell::new((Config::DEFAULT, None)));
+
+/// Set when a panic starts, so that a panic while reporting it halts right away.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Set the outputs panics get reported to.
+pub fn set_config(config: Config) {
+    STATE.with(|state| state.0 = config);
+}
+
+/// Get the outputs panics get reported to.
+pub fn config() -> Config {
+    STATE.with(|state| state.0)
+}
+
+/// Set the function called once a panic has been reported, replacing the previous one.
+///
+/// **NOTE**: A panic in the hook halts the processor without reporting anything more.
+pub fn set_hook(hook: Hook) {
+    STATE.with(|state| state.1 = Some(hook));
+}
+
+/// Remove the function called once a panic has been reported, and return it.
+pub fn take_hook() -> Option<Hook> {
+    STATE.with(|state| state.1.take())
+}
+
+/// Maximum number of frames in a ``Backtrace``.
+const MAX_FRAMES: usize = 16;
+
+/// A stack trace, made of the addresses of the calls in each frame, innermost first.
+#[derive(Debug, Clone, Copy)]
+pub struct Backtrace {
+    frames: [u32; MAX_FRAMES],
+    len: usize,
+}
+
+/// Whether ``address`` may point to a stack frame, aligned in MEM1 or MEM2.
+fn is_frame(address: u32) -> bool {
+    address & 7 == 0
+        && ((0x8000_0000..0x8180_0000).contains(&address)
+            || (0x9000_0000..0x9400_0000).contains(&address))
+}
+
+impl Backtrace {
+    /// A stack trace without any frame.
+    pub const fn empty() -> Backtrace {
+        Backtrace {
+            frames: [0; MAX_FRAMES],
+            len: 0,
+        }
+    }
+
+    /// Walk the back chain of the stack from the caller, up to 16 frames deep.
+    ///
+    /// Each frame holds a pointer to the previous one, and in its second word the return address
+    /// saved by its callee.  The walk stops at the null back chain of the outermost frame, or at
+    /// anything that doesn't look like a frame.
+    #[inline(never)]
+    pub fn capture() -> Backtrace {
+        // Calling ``walk`` gives this function a frame of its own, whose back chain is the frame
+        // of the caller, where the return address to it got saved.
+        let frame = register::stack_pointer();
+        if !is_frame(frame) {
+            return Backtrace::empty();
+        }
+        Backtrace::walk(unsafe { ptr::read_volatile(frame as *const u32) })
+    }
+
+    /// Walk the back chain of the stack from ``frame``.
+    #[inline(never)]
+    fn walk(mut frame: u32) -> Backtrace {
+        let mut backtrace = Backtrace::empty();
+
+        while backtrace.len < MAX_FRAMES && is_frame(frame) {
+            let lr = unsafe { ptr::read_volatile((frame + 4) as *const u32) };
+            if lr < 4 {
+                break;
+            }
+
+            // The call is the instruction before the return address.
+            backtrace.frames[backtrace.len] = lr - 4;
+            backtrace.len += 1;
+
+            let previous = unsafe { ptr::read_volatile(frame as *const u32) };
+            if previous <= frame {
+                break;
+            }
+            frame = previous;
+        }
+
+        backtrace
+    }
+
+    /// Get the addresses of the calls, innermost first.
+    pub fn frames(&self) -> &[u32] {
+        &self.frames[..self.len]
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "stack backtrace:")?;
+        for (n, address) in self.frames().iter().enumerate() {
+            writeln!(f, "{:>4}: {:#010x}", n, address)?;
+        }
+        Ok(())
+    }
+}
+
+/// The text of a panic report.
+struct Report<'a> {
+    info: &'a PanicInfo<'a>,
+    backtrace: &'a Backtrace,
+    /// High-water mark of the main stack.
+    stack_used: usize,
+    /// Whether the main stack grew into its guard.
+    overflowed: bool,
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.info)?;
+        write!(
+            f,
+            "main stack: {:#x} of {:#x} bytes used",
+            self.stack_used,
+            stack::size()
+        )?;
+        if self.overflowed {
+            write!(f, ", overflowed into its guard")?;
+        }
+        writeln!(f)?;
+        if !self.backtrace.frames().is_empty() {
+            write!(f, "{}", self.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+/// Scale of the glyphs on screen, each pixel of the font taking one YUYV word.
+const SCALE: usize = 2;
+
+/// Space around the text on screen, in pixels.
+const MARGIN: usize = 32;
+
+/// Background of the red screen, as a pair of YUYV pixels.
+const RED: u32 = 0x525a_52f0;
+
+/// Text of the red screen, as a pair of YUYV pixels.
+const WHITE: u32 = 0xeb80_eb80;
+
+/// A ``fmt::Write`` implementation drawing the text over the XFB being scanned out.
+struct Screen {
+    xfb: *mut u32,
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+}
+
+impl Screen {
+    /// Paint the whole XFB red, or return ``None`` if the display is off.
+    fn new() -> Option<Screen> {
+        let (xfb, width, height) = vi::scanout()?;
+        let xfb = xfb as *mut u32;
+        for n in 0..width / 2 * height {
+            unsafe { ptr::write_volatile(xfb.add(n), RED) };
+        }
+        Some(Screen {
+            xfb,
+            width,
+            height,
+            x: MARGIN,
+            y: MARGIN,
+        })
+    }
+
+    fn new_line(&mut self) {
+        self.x = MARGIN;
+        self.y += (font::HEIGHT + 2) * SCALE;
+    }
+
+    fn draw(&mut self, c: char) {
+        if self.x + font::WIDTH * SCALE > self.width - MARGIN {
+            self.new_line();
+        }
+        if self.y + font::HEIGHT * SCALE > self.height - MARGIN {
+            return;
+        }
+
+        let glyph = font::glyph(c);
+        for gy in 0..font::HEIGHT * SCALE {
+            let line = (self.y + gy) * self.width / 2;
+            for gx in 0..font::WIDTH {
+                if font::is_set(&glyph, gx, gy / SCALE) {
+                    let word = line + self.x / 2 + gx;
+                    unsafe { ptr::write_volatile(self.xfb.add(word), WHITE) };
+                }
+            }
+        }
+        self.x += (font::WIDTH + 1) * SCALE;
+    }
+}
+
+impl fmt::Write for Screen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '\n' => self.new_line(),
+                c => self.draw(c),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Report a panic to the configured outputs, run the hook, then halt the processor.
+pub(crate) fn handle(info: &PanicInfo<'_>) -> ! {
+    processor::cpu_isr_disable();
+
+    if !PANICKING.swap(true, Ordering::Relaxed) {
+        let (config, hook) = unsafe { *STATE.0.get() };
+        let backtrace = if config.backtrace {
+            Backtrace::capture()
+        } else {
+            Backtrace::empty()
+        };
+        let report = Report {
+            info,
+            backtrace: &backtrace,
+            stack_used: stack::high_water_mark(),
+            overflowed: stack::is_overflowed(),
+        };
+
+        if config.gecko && crate::diag::is_connected() {
+            let _ = write!(Gecko, "{}", report);
+        }
+        if config.dolphin {
+            let _ = write!(Uart, "{}", report);
+        }
+        if config.screen {
+            if let Some(mut screen) = Screen::new() {
+                let _ = write!(screen, "{}", report);
+            }
+        }
+        if let Some(hook) = hook {
+            hook(info, &backtrace);
+        }
+    }
+
+    loop {
+        processor::ppc_halt();
+    }
+}
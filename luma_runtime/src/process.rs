@@ -0,0 +1,214 @@
+//! ``process`` module of ``luma_runtime``.
+//!
+//! Contains the ways out of the program: back to the loader, or a reset, power off or reboot of
+//! the console.
+//!
+//! Loaders such as the Homebrew Channel leave a reload stub at ``0x8000_1800``, tagged with
+//! ``"STUBHAXX"``, which brings them back once the program jumps to it.  Without one, the
+//! console is reset instead.
+
+use core::fmt::Debug;
+use luma_core::cache::{DCFlushRange, ICFlashInvalidate};
+use luma_core::io::read32;
+#[cfg(not(feature = "gamecube"))]
+use luma_core::ipc::{Fd, Vector};
+use luma_core::{pi, processor, vi};
+
+/// Address of the reload stub.
+const STUB_ENTRY: u32 = 0x8000_1800;
+
+/// Tag of the reload stub, ``"STUBHAXX"``.
+const STUB_MAGIC: [u32; 2] = [0x5354_5542, 0x4841_5858];
+
+/// ``/dev/stm/immediate`` ioctl resetting the console.
+#[cfg(not(feature = "gamecube"))]
+const STM_HOT_RESET: u32 = 0x2001;
+
+/// ``/dev/stm/immediate`` ioctl powering the console off.
+#[cfg(not(feature = "gamecube"))]
+const STM_SHUTDOWN: u32 = 0x2003;
+
+/// ``/dev/es`` ioctlv launching a title.
+#[cfg(not(feature = "gamecube"))]
+const ES_LAUNCH_TITLE: u32 = 0x08;
+
+/// ``/dev/es`` ioctlv getting the number of ticket views of a title.
+#[cfg(not(feature = "gamecube"))]
+const ES_GET_TICKET_VIEW_COUNT: u32 = 0x12;
+
+/// ``/dev/es`` ioctlv getting the ticket views of a title.
+#[cfg(not(feature = "gamecube"))]
+const ES_GET_TICKET_VIEWS: u32 = 0x13;
+
+/// Size of a ticket view.
+#[cfg(not(feature = "gamecube"))]
+const TICKET_VIEW_SIZE: usize = 0xd8;
+
+/// Title ID of the System Menu.
+#[cfg(not(feature = "gamecube"))]
+const SYSTEM_MENU: u64 = 0x0000_0001_0000_0002;
+
+/// A buffer aligned for IOS and the cache operations.
+#[repr(C, align(32))]
+struct Aligned<T>(T);
+
+/// An exit code, as returned by ``main`` or passed to ``exit``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExitCode(i32);
+
+impl ExitCode {
+    /// The exit code of a successful run.
+    pub const SUCCESS: ExitCode = ExitCode(0);
+
+    /// The exit code of a failed run.
+    pub const FAILURE: ExitCode = ExitCode(1);
+
+    /// Create an exit code from its value.
+    pub const fn new(code: i32) -> ExitCode {
+        ExitCode(code)
+    }
+
+    /// Get the value of this exit code.
+    pub const fn code(self) -> i32 {
+        self.0
+    }
+}
+
+impl From<u8> for ExitCode {
+    fn from(code: u8) -> ExitCode {
+        ExitCode(code as i32)
+    }
+}
+
+/// A type ``main`` can return, which reports the exit code of the program.
+#[cfg_attr(not(test), lang = "termination")]
+pub trait Termination {
+    /// Report this result, then return the exit code it maps to.
+    fn report(self) -> i32;
+}
+
+impl Termination for () {
+    fn report(self) -> i32 {
+        ExitCode::SUCCESS.code()
+    }
+}
+
+impl Termination for ExitCode {
+    fn report(self) -> i32 {
+        self.code()
+    }
+}
+
+impl<T: Termination, E: Debug> Termination for Result<T, E> {
+    /// Report an error through the diagnostic output, as a failure.
+    fn report(self) -> i32 {
+        match self {
+            Ok(value) => value.report(),
+            Err(error) => {
+                crate::eprintln!("Error: {:?}", error);
+                ExitCode::FAILURE.code()
+            }
+        }
+    }
+}
+
+/// Whether the loader left a reload stub to return to.
+pub fn has_loader_stub() -> bool {
+    read32(STUB_ENTRY + 4) == STUB_MAGIC[0] && read32(STUB_ENTRY + 8) == STUB_MAGIC[1]
+}
+
+/// Stop the video, the interrupts and the caches, before handing the console over.
+fn shutdown() {
+    processor::cpu_isr_disable();
+    vi::shutdown();
+    pi::init();
+    unsafe {
+        DCFlushRange(0x8000_0000 as *const u32, 0x0180_0000);
+        ICFlashInvalidate();
+    }
+}
+
+/// Exit the program, returning to the loader when it left a reload stub and resetting the
+/// console otherwise.
+///
+/// **NOTE**: Nothing receives ``code``, there is no shell on the console, but a failure is
+/// reported through the diagnostic output.
+pub fn exit(code: i32) -> ! {
+    if code != ExitCode::SUCCESS.code() {
+        crate::eprintln!("Exiting with code {}", code);
+    }
+    if has_loader_stub() {
+        shutdown();
+        let stub: extern "C" fn() -> ! = unsafe { core::mem::transmute(STUB_ENTRY as usize) };
+        stub();
+    }
+    reset()
+}
+
+/// Send an ioctl to ``/dev/stm/immediate``, which only returns on failure.
+#[cfg(not(feature = "gamecube"))]
+fn stm_immediate(ioctl: u32) {
+    let input = Aligned([0u8; 0x20]);
+    let mut output = Aligned([0u8; 0x20]);
+    if let Ok(stm) = Fd::open(b"/dev/stm/immediate\0", 0) {
+        let _ = stm.ioctl(ioctl, &input.0, &mut output.0);
+    }
+}
+
+/// Reset the console, which boots into the System Menu on a Wii.
+pub fn reset() -> ! {
+    shutdown();
+
+    #[cfg(not(feature = "gamecube"))]
+    stm_immediate(STM_HOT_RESET);
+
+    pi::hot_reset()
+}
+
+/// Power the console off, or to standby depending on the WiiConnect24 settings.
+///
+/// **NOTE**: This function only halts the processor if IOS refuses the request.
+#[cfg(not(feature = "gamecube"))]
+pub fn power_off() -> ! {
+    shutdown();
+    stm_immediate(STM_SHUTDOWN);
+    loop {
+        processor::ppc_halt();
+    }
+}
+
+/// Reboot into the System Menu by launching it through ``/dev/es``, without going through the
+/// boot sequence again.
+///
+/// **NOTE**: This function resets the console if IOS refuses to launch the System Menu.
+#[cfg(not(feature = "gamecube"))]
+pub fn reboot_to_system_menu() -> ! {
+    shutdown();
+
+    if let Ok(es) = Fd::open(b"/dev/es\0", 0) {
+        let title = Aligned(SYSTEM_MENU.to_be_bytes());
+        let mut count = Aligned([0u8; 4]);
+        let mut view = Aligned([0u8; TICKET_VIEW_SIZE]);
+
+        unsafe {
+            let vectors = Aligned([Vector::input(&title.0), Vector::output(&mut count.0)]);
+            let has_view = es.ioctlv(ES_GET_TICKET_VIEW_COUNT, 1, &vectors.0).is_ok()
+                && u32::from_be_bytes(count.0) > 0;
+
+            // Only the first view is needed, ask for a single one.
+            count.0 = 1u32.to_be_bytes();
+            let vectors = Aligned([
+                Vector::input(&title.0),
+                Vector::input(&count.0),
+                Vector::output(&mut view.0),
+            ]);
+            if has_view && es.ioctlv(ES_GET_TICKET_VIEWS, 2, &vectors.0).is_ok() {
+                // On success IOS reloads and resets Broadway, so this never returns.
+                let vectors = Aligned([Vector::input(&title.0), Vector::input(&view.0)]);
+                let _ = es.ioctlv(ES_LAUNCH_TITLE, 2, &vectors.0);
+            }
+        }
+    }
+
+    reset()
+}
@@ -0,0 +1,140 @@
+//! ``stack`` module of ``luma_runtime``.
+//!
+//! Contains the overflow detection and usage reporting of the main stack.
+//!
+//! The main stack grows down from ``__stack_addr``, where the heap starts, to ``__stack_end``,
+//! where ``.bss`` ends.  Its lowest ``__stack_guard_size`` bytes are a guard, which the program
+//! should never reach: the sizes of both come from the linker script, and can be changed with
+//! ``-Wl,--defsym,__stack_size=SIZE`` and ``-Wl,--defsym,__stack_guard_size=SIZE``.
+//!
+//! The whole free stack is painted with a canary at startup.  A write to the top of the guard
+//! traps right away through the data address breakpoint, and any other write to the guard is
+//! caught by checking the canary periodically and when panicking.  The lowest word which lost
+//! its canary also gives the high-water mark of the stack.
+//!
+//! **NOTE**: Only the main stack is covered, threads get their stacks from the heap.
+
+use core::ops::Range;
+use core::ptr;
+use core::time::Duration;
+use luma_core::alarm;
+use luma_core::exception::{self, Context, Exception};
+use luma_core::register;
+
+extern "C" {
+    static __stack_guard_size: u8;
+}
+
+/// Value painted over the free stack, ``"STCK"``.
+const CANARY: u32 = 0x5354_434b;
+
+/// Space left unpainted below the stack pointer of ``init``, for its own callees.
+const MARGIN: usize = 0x100;
+
+/// Period of the canary check.
+const CHECK_PERIOD: Duration = Duration::from_millis(100);
+
+/// DABR[BT|DW], match data writes with address translation on.
+const DABR_WRITE: u32 = 0x6;
+
+/// DSISR bit of a data address breakpoint match.
+const DSISR_DABR: u32 = 0x0040_0000;
+
+/// Get the addresses of the main stack, from its lowest to its top.
+pub fn bounds() -> Range<usize> {
+    let end = unsafe { &crate::__stack_end } as *const _ as usize;
+    let addr = unsafe { &crate::__stack_addr } as *const _ as usize;
+    end..addr
+}
+
+/// Get the addresses of the guard at the bottom of the main stack.
+pub fn guard() -> Range<usize> {
+    let size = unsafe { &__stack_guard_size } as *const u8 as usize;
+    let bounds = bounds();
+    bounds.start..bounds.start + size
+}
+
+/// Get the size of the main stack in bytes, its guard included.
+pub fn size() -> usize {
+    bounds().len()
+}
+
+/// Whether every word of ``range`` still holds the canary.
+fn is_painted(range: Range<usize>) -> bool {
+    range
+        .step_by(4)
+        .all(|address| unsafe { ptr::read_volatile(address as *const u32) } == CANARY)
+}
+
+/// Get the most bytes the main stack ever used, never less than its depth when the runtime
+/// painted it.
+pub fn high_water_mark() -> usize {
+    let bounds = bounds();
+    let lowest = (bounds.start..bounds.end)
+        .step_by(4)
+        .find(|&address| unsafe { ptr::read_volatile(address as *const u32) } != CANARY)
+        .unwrap_or(bounds.end);
+    bounds.end - lowest
+}
+
+/// Whether the main stack grew into its guard.
+pub fn is_overflowed() -> bool {
+    !is_painted(guard())
+}
+
+/// Check the guard of the main stack, called periodically and when panicking.
+///
+/// **NOTE**: This function panics if the main stack grew into its guard.
+pub fn check() {
+    if is_overflowed() {
+        set_watchpoint(false);
+        panic!(
+            "Main stack overflow, {:#x} of {:#x} bytes used",
+            high_water_mark(),
+            size()
+        );
+    }
+}
+
+/// Arm or disarm the data address breakpoint on the top of the guard.
+///
+/// **NOTE**: The breakpoint is the only one of the processor, it has to be disarmed before being
+/// used for anything else.  It never fires from exception handlers, which the periodic check
+/// covers instead.
+pub fn set_watchpoint(enabled: bool) {
+    let dabr = if enabled {
+        (guard().end as u32 - 8) | DABR_WRITE
+    } else {
+        0
+    };
+    exception::set_data_breakpoint(dabr);
+}
+
+/// Report a write to the top of the guard as a stack overflow, hand other DSIs to luma.
+fn dsi_handler(exception: Exception, context: &mut Context) {
+    if context.dsisr & DSISR_DABR == 0 {
+        exception::default_handler(exception, context);
+        return;
+    }
+
+    // The panic runs on what is left of the guard, keep the breakpoint off from now on.
+    set_watchpoint(false);
+    panic!(
+        "Main stack overflow, write to {:08x} in the guard at {:08x}",
+        context.dar, context.srr0
+    );
+}
+
+/// Paint the free main stack with the canary, arm the watchpoint and start the periodic check,
+/// called by the runtime once alarms and exceptions are set up.
+pub(crate) fn init() {
+    let bounds = bounds();
+    let top = (register::stack_pointer() as usize - MARGIN).min(bounds.end);
+    for address in (bounds.start..top).step_by(4) {
+        unsafe { ptr::write_volatile(address as *mut u32, CANARY) };
+    }
+
+    exception::set_handler(Exception::Dsi, dsi_handler);
+    set_watchpoint(true);
+    alarm::set_periodic_alarm(CHECK_PERIOD, CHECK_PERIOD, check);
+}
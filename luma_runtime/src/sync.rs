@@ -0,0 +1,532 @@
+//! ``sync`` module of ``luma_runtime``.
+//!
+//! Contains blocking synchronisation primitives between threads.
+//!
+//! Every primitive keeps its state behind interrupt masking, and blocks contending threads on a
+//! ``thread::WaitQueue`` so that other threads run meanwhile.  ``Mutex`` and ``RwLock`` are the
+//! ``lock_api`` types, instantiated with the raw locks of this module.
+//!
+//! In debug builds, a thread locking a mutex it already holds, directly or through a chain of
+//! threads waiting on each other's mutexes, panics instead of hanging forever.
+//!
+//! **NOTE**: Blocking isn't possible from exception handlers, which may only use the ``try_``
+//! variants and the release or notify functions.
+
+use crate::thread::{self, ThreadId, WaitQueue};
+use core::cell::UnsafeCell;
+use core::time::Duration;
+use luma_core::processor;
+
+/// A mutual exclusion lock, blocking the threads contending for it.
+pub type Mutex<T> = lock_api::Mutex<RawMutex, T>;
+
+/// An RAII guard of a locked ``Mutex``.
+pub type MutexGuard<'a, T> = lock_api::MutexGuard<'a, RawMutex, T>;
+
+/// A reader-writer lock, blocking the threads contending for it.
+pub type RwLock<T> = lock_api::RwLock<RawRwLock, T>;
+
+/// An RAII guard of a ``RwLock`` locked for reading.
+pub type RwLockReadGuard<'a, T> = lock_api::RwLockReadGuard<'a, RawRwLock, T>;
+
+/// An RAII guard of a ``RwLock`` locked for writing.
+pub type RwLockWriteGuard<'a, T> = lock_api::RwLockWriteGuard<'a, RawRwLock, T>;
+
+/// Create a ``Mutex`` in a constant context, for instance in a ``static``.
+pub const fn const_mutex<T>(value: T) -> Mutex<T> {
+    Mutex::const_new(<RawMutex as lock_api::RawMutex>::INIT, value)
+}
+
+/// Create a ``RwLock`` in a constant context, for instance in a ``static``.
+pub const fn const_rwlock<T>(value: T) -> RwLock<T> {
+    RwLock::const_new(<RawRwLock as lock_api::RawRwLock>::INIT, value)
+}
+
+struct MutexState {
+    locked: bool,
+    owner: Option<ThreadId>,
+}
+
+/// The raw mutex behind ``Mutex``.
+pub struct RawMutex {
+    state: UnsafeCell<MutexState>,
+    waiters: WaitQueue,
+}
+
+unsafe impl Sync for RawMutex {}
+unsafe impl Send for RawMutex {}
+
+/// Mutex each blocked thread waits on, for deadlock detection.
+#[cfg(debug_assertions)]
+struct WaitGraph(UnsafeCell<alloc::vec::Vec<(ThreadId, *const RawMutex)>>);
+
+#[cfg(debug_assertions)]
+unsafe impl Sync for WaitGraph {}
+
+#[cfg(debug_assertions)]
+static WAIT_GRAPH: WaitGraph = WaitGraph(UnsafeCell::new(alloc::vec::Vec::new()));
+
+impl RawMutex {
+    /// Get the state of the mutex, interrupts have to be disabled.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn state(&self) -> &mut MutexState {
+        &mut *self.state.get()
+    }
+
+    /// Take the mutex if it is free, interrupts have to be disabled.
+    unsafe fn acquire(&self, thread: ThreadId) -> bool {
+        let state = self.state();
+        if state.locked {
+            return false;
+        }
+        state.locked = true;
+        state.owner = Some(thread);
+        true
+    }
+
+    /// Whether waiting on this mutex would never return, interrupts have to be disabled.
+    #[cfg(debug_assertions)]
+    unsafe fn would_deadlock(&self, thread: ThreadId) -> bool {
+        let graph = &*WAIT_GRAPH.0.get();
+        let mut owner = self.state().owner;
+        while let Some(holder) = owner {
+            if holder == thread {
+                return true;
+            }
+            owner = graph
+                .iter()
+                .find(|&&(waiter, _)| waiter == holder)
+                .and_then(|&(_, mutex)| (*mutex).state().owner);
+        }
+        false
+    }
+
+    /// Record that the thread waits on this mutex, or stopped waiting on any.
+    #[cfg(debug_assertions)]
+    unsafe fn set_waiting(thread: ThreadId, mutex: Option<&RawMutex>) {
+        let graph = &mut *WAIT_GRAPH.0.get();
+        graph.retain(|&(waiter, _)| waiter != thread);
+        if let Some(mutex) = mutex {
+            graph.push((thread, mutex));
+        }
+    }
+}
+
+unsafe impl lock_api::RawMutex for RawMutex {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: RawMutex = RawMutex {
+        state: UnsafeCell::new(MutexState {
+            locked: false,
+            owner: None,
+        }),
+        waiters: WaitQueue::new(),
+    };
+
+    type GuardMarker = lock_api::GuardNoSend;
+
+    fn lock(&self) {
+        let thread = thread::current();
+        let acquired = self.waiters.wait_until(|| unsafe {
+            if self.acquire(thread) {
+                #[cfg(debug_assertions)]
+                RawMutex::set_waiting(thread, None);
+                return Some(true);
+            }
+            #[cfg(debug_assertions)]
+            {
+                if self.would_deadlock(thread) {
+                    RawMutex::set_waiting(thread, None);
+                    return Some(false);
+                }
+                RawMutex::set_waiting(thread, Some(self));
+            }
+            None
+        });
+        assert!(acquired, "Deadlock: {:?} waits on a mutex it holds", thread);
+    }
+
+    fn try_lock(&self) -> bool {
+        let thread = thread::current();
+        processor::critical_section(|| unsafe { self.acquire(thread) })
+    }
+
+    unsafe fn unlock(&self) {
+        processor::critical_section(|| {
+            let state = self.state();
+            state.locked = false;
+            state.owner = None;
+        });
+        self.waiters.notify_one();
+    }
+
+    fn is_locked(&self) -> bool {
+        processor::critical_section(|| unsafe { self.state().locked })
+    }
+}
+
+struct RwLockState {
+    readers: usize,
+    writer: Option<ThreadId>,
+}
+
+/// The raw reader-writer lock behind ``RwLock``.
+pub struct RawRwLock {
+    state: UnsafeCell<RwLockState>,
+    waiters: WaitQueue,
+}
+
+unsafe impl Sync for RawRwLock {}
+unsafe impl Send for RawRwLock {}
+
+impl RawRwLock {
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn state(&self) -> &mut RwLockState {
+        &mut *self.state.get()
+    }
+
+    /// Take the lock for reading if there is no writer, interrupts have to be disabled.
+    unsafe fn acquire_shared(&self) -> bool {
+        let state = self.state();
+        if state.writer.is_some() {
+            return false;
+        }
+        state.readers += 1;
+        true
+    }
+
+    /// Take the lock for writing if it is free, interrupts have to be disabled.
+    unsafe fn acquire_exclusive(&self, thread: ThreadId) -> bool {
+        let state = self.state();
+        if state.writer.is_some() || state.readers != 0 {
+            return false;
+        }
+        state.writer = Some(thread);
+        true
+    }
+
+    #[cfg(debug_assertions)]
+    fn check_writer(&self, thread: ThreadId) {
+        let writer = processor::critical_section(|| unsafe { self.state().writer });
+        assert!(
+            writer != Some(thread),
+            "Deadlock: {:?} waits on a lock it holds for writing",
+            thread
+        );
+    }
+}
+
+unsafe impl lock_api::RawRwLock for RawRwLock {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: RawRwLock = RawRwLock {
+        state: UnsafeCell::new(RwLockState {
+            readers: 0,
+            writer: None,
+        }),
+        waiters: WaitQueue::new(),
+    };
+
+    type GuardMarker = lock_api::GuardNoSend;
+
+    fn lock_shared(&self) {
+        #[cfg(debug_assertions)]
+        self.check_writer(thread::current());
+        self.waiters.wait_until(|| unsafe {
+            if self.acquire_shared() {
+                Some(())
+            } else {
+                None
+            }
+        });
+    }
+
+    fn try_lock_shared(&self) -> bool {
+        processor::critical_section(|| unsafe { self.acquire_shared() })
+    }
+
+    unsafe fn unlock_shared(&self) {
+        let last = processor::critical_section(|| {
+            let state = self.state();
+            state.readers -= 1;
+            state.readers == 0
+        });
+        if last {
+            self.waiters.notify_all();
+        }
+    }
+
+    fn lock_exclusive(&self) {
+        let thread = thread::current();
+        #[cfg(debug_assertions)]
+        self.check_writer(thread);
+        self.waiters.wait_until(|| unsafe {
+            if self.acquire_exclusive(thread) {
+                Some(())
+            } else {
+                None
+            }
+        });
+    }
+
+    fn try_lock_exclusive(&self) -> bool {
+        let thread = thread::current();
+        processor::critical_section(|| unsafe { self.acquire_exclusive(thread) })
+    }
+
+    unsafe fn unlock_exclusive(&self) {
+        processor::critical_section(|| self.state().writer = None);
+        self.waiters.notify_all();
+    }
+
+    fn is_locked(&self) -> bool {
+        processor::critical_section(|| unsafe {
+            let state = self.state();
+            state.writer.is_some() || state.readers != 0
+        })
+    }
+}
+
+/// A counting semaphore.
+pub struct Semaphore {
+    count: UnsafeCell<usize>,
+    waiters: WaitQueue,
+}
+
+unsafe impl Sync for Semaphore {}
+unsafe impl Send for Semaphore {}
+
+impl Semaphore {
+    /// Create a semaphore with ``count`` permits available.
+    pub const fn new(count: usize) -> Semaphore {
+        Semaphore {
+            count: UnsafeCell::new(count),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Take a permit if one is available, interrupts have to be disabled.
+    unsafe fn take(&self) -> Option<()> {
+        let count = &mut *self.count.get();
+        if *count == 0 {
+            return None;
+        }
+        *count -= 1;
+        Some(())
+    }
+
+    /// Block until a permit is available, and take it.
+    pub fn acquire(&self) {
+        self.waiters.wait_until(|| unsafe { self.take() });
+    }
+
+    /// Take a permit if one is available, returns whether one was.
+    pub fn try_acquire(&self) -> bool {
+        processor::critical_section(|| unsafe { self.take() }).is_some()
+    }
+
+    /// Block until a permit is available or ``timeout`` elapsed, returns whether one was taken.
+    pub fn acquire_timeout(&self, timeout: Duration) -> bool {
+        self.waiters
+            .wait_until_timeout(timeout, || unsafe { self.take() })
+            .is_some()
+    }
+
+    /// Give a permit back, waking up a waiting thread.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn release(&self) {
+        processor::critical_section(|| unsafe { *self.count.get() += 1 });
+        self.waiters.notify_one();
+    }
+
+    /// Get the number of permits currently available.
+    pub fn available(&self) -> usize {
+        processor::critical_section(|| unsafe { *self.count.get() })
+    }
+}
+
+/// A condition variable, to wait for a ``Mutex``-protected state to change.
+#[derive(Default)]
+pub struct Condvar {
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    /// Create a condition variable.
+    pub const fn new() -> Condvar {
+        Condvar {
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Unlock the mutex of ``guard`` and block until notified, then lock it again.
+    ///
+    /// Wake-ups may be spurious, so the protected state has to be checked again afterwards.
+    pub fn wait<T>(&self, guard: &mut MutexGuard<'_, T>) {
+        self.wait_inner(guard, None);
+    }
+
+    /// Block with ``wait`` for as long as ``condition`` holds.
+    pub fn wait_while<T>(
+        &self,
+        guard: &mut MutexGuard<'_, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) {
+        while condition(&mut *guard) {
+            self.wait(guard);
+        }
+    }
+
+    /// Same as ``wait``, but give up once ``timeout`` has elapsed.  Returns whether it did.
+    pub fn wait_timeout<T>(&self, guard: &mut MutexGuard<'_, T>, timeout: Duration) -> bool {
+        self.wait_inner(guard, Some(timeout))
+    }
+
+    fn wait_inner<T>(&self, guard: &mut MutexGuard<'_, T>, timeout: Option<Duration>) -> bool {
+        let mutex = unsafe { MutexGuard::mutex(guard).raw() };
+
+        // Queue up before unlocking, so that no notification gets lost in between.
+        let notified = self
+            .waiters
+            .wait_notified(timeout, || unsafe { lock_api::RawMutex::unlock(mutex) });
+
+        lock_api::RawMutex::lock(mutex);
+        !notified
+    }
+
+    /// Wake up one waiting thread, returns whether there was one.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn notify_one(&self) -> bool {
+        self.waiters.notify_one()
+    }
+
+    /// Wake up every waiting thread, returns how many there were.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn notify_all(&self) -> usize {
+        self.waiters.notify_all()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OnceState {
+    Incomplete,
+    Running,
+    Complete,
+}
+
+/// A one-time initialization.
+pub struct Once {
+    state: UnsafeCell<OnceState>,
+    waiters: WaitQueue,
+}
+
+unsafe impl Sync for Once {}
+unsafe impl Send for Once {}
+
+impl Once {
+    /// Create a ``Once`` which didn't run yet.
+    pub const fn new() -> Once {
+        Once {
+            state: UnsafeCell::new(OnceState::Incomplete),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Run ``f`` if no other call did, blocking until the call which runs it returns.
+    pub fn call_once(&self, f: impl FnOnce()) {
+        let run = self.waiters.wait_until(|| unsafe {
+            let state = &mut *self.state.get();
+            match *state {
+                OnceState::Incomplete => {
+                    *state = OnceState::Running;
+                    Some(true)
+                }
+                OnceState::Running => None,
+                OnceState::Complete => Some(false),
+            }
+        });
+        if run {
+            f();
+            processor::critical_section(|| unsafe { *self.state.get() = OnceState::Complete });
+            self.waiters.notify_all();
+        }
+    }
+
+    /// Whether a ``call_once`` has returned.
+    pub fn is_completed(&self) -> bool {
+        processor::critical_section(|| unsafe { *self.state.get() == OnceState::Complete })
+    }
+}
+
+impl Default for Once {
+    fn default() -> Once {
+        Once::new()
+    }
+}
+
+struct BarrierState {
+    count: usize,
+    generation: usize,
+}
+
+/// A barrier, blocking threads until all of them reach it.
+pub struct Barrier {
+    threads: usize,
+    state: UnsafeCell<BarrierState>,
+    waiters: WaitQueue,
+}
+
+unsafe impl Sync for Barrier {}
+unsafe impl Send for Barrier {}
+
+/// Returned by ``Barrier::wait``, tells a single thread of each round apart.
+#[derive(Debug, Clone, Copy)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Whether this thread was the last one to reach the barrier.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    /// Create a barrier for ``threads`` threads.
+    pub const fn new(threads: usize) -> Barrier {
+        Barrier {
+            threads,
+            state: UnsafeCell::new(BarrierState {
+                count: 0,
+                generation: 0,
+            }),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Block until ``threads`` threads called this function, then release them all.
+    pub fn wait(&self) -> BarrierWaitResult {
+        let mut generation = None;
+        let leader = self.waiters.wait_until(|| unsafe {
+            let state = &mut *self.state.get();
+            match generation {
+                None if state.count + 1 >= self.threads => {
+                    state.count = 0;
+                    state.generation = state.generation.wrapping_add(1);
+                    Some(true)
+                }
+                None => {
+                    state.count += 1;
+                    generation = Some(state.generation);
+                    None
+                }
+                Some(generation) if generation != state.generation => Some(false),
+                Some(_) => None,
+            }
+        });
+        if leader {
+            self.waiters.notify_all();
+        }
+        BarrierWaitResult(leader)
+    }
+}
@@ -0,0 +1,803 @@
+//! ``thread`` module of ``luma_runtime``.
+//!
+//! Contains preemptive threads, each running on its own stack at its own priority.
+//!
+//! The highest priority ready thread always runs, and threads of equal priority take turns every
+//! time slice.  Threads are switched on the way out of exceptions, by the scheduler installed in
+//! ``luma_core::exception``: when a thread yields or blocks, when an interrupt handler wakes up a
+//! more urgent thread, or when a time slice ends.  Along with the context saved by the exception
+//! vectors, every thread has its own graphics quantization registers.
+//!
+//! **NOTE**: Priority ``0`` is reserved to the idle thread, which dozes until the next interrupt.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cell::UnsafeCell;
+use core::mem;
+use core::time::Duration;
+use luma_core::exception::{self, Context};
+use luma_core::gqr::{self, Gqr};
+use luma_core::time::Instant;
+use luma_core::{alarm, processor, register};
+
+/// Stack size of threads spawned without a ``Builder``.
+pub const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// Priority of the main thread, and of threads spawned without a ``Builder``.
+pub const DEFAULT_PRIORITY: u8 = 64;
+
+/// Time after which a thread lets others of the same priority run.
+const TIME_SLICE: Duration = Duration::from_millis(10);
+
+/// Stack size of the idle thread.
+const IDLE_STACK_SIZE: usize = 4 * 1024;
+
+/// Identifier of the main thread.
+const MAIN: ThreadId = ThreadId(0);
+
+/// Unique identifier of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ThreadId(u32);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Ready,
+    Blocked,
+    Finished,
+}
+
+struct Thread {
+    priority: u8,
+    state: State,
+    /// Context to resume, saved when the thread got switched out.
+    context: *mut Context,
+    /// Graphics quantization registers.
+    gqr: [Gqr; 8],
+    /// Set by ``unpark`` while the thread wasn't blocked, consumed by the next ``park``.
+    unparked: bool,
+    /// Stack of the thread, ``None`` for the main thread which runs on the crt0 stack.
+    stack: Option<Box<[u8]>>,
+    /// Thread-local values, keyed by the address of their ``LocalKey``.
+    locals: BTreeMap<usize, Box<dyn Any>>,
+}
+
+impl Thread {
+    fn new(priority: u8, state: State, stack: Option<Box<[u8]>>) -> Thread {
+        Thread {
+            priority,
+            state,
+            context: core::ptr::null_mut(),
+            gqr: [Gqr::default(); 8],
+            unparked: false,
+            stack,
+            locals: BTreeMap::new(),
+        }
+    }
+}
+
+struct Scheduler {
+    threads: BTreeMap<ThreadId, Thread>,
+    /// Ready threads, in the order they became ready.
+    ready: VecDeque<ThreadId>,
+    current: ThreadId,
+    next_id: u32,
+    /// Whether the running thread should be switched out on the way out of the next exception.
+    need_resched: bool,
+    /// Stacks of finished threads, freed outside of the scheduler.
+    zombies: Vec<Box<[u8]>>,
+}
+
+impl Scheduler {
+    fn current_mut(&mut self) -> &mut Thread {
+        self.threads.get_mut(&self.current).unwrap()
+    }
+
+    /// Make a blocked thread ready, or let its next ``park`` return immediately.
+    fn unpark(&mut self, id: ThreadId) {
+        let current_priority = self.current_mut().priority;
+        let thread = match self.threads.get_mut(&id) {
+            Some(thread) => thread,
+            None => return,
+        };
+        match thread.state {
+            State::Blocked => {
+                thread.state = State::Ready;
+                if thread.priority > current_priority {
+                    self.need_resched = true;
+                }
+                self.ready.push_back(id);
+            }
+            State::Finished => (),
+            _ => thread.unparked = true,
+        }
+    }
+
+    /// Take the first of the highest priority ready threads.
+    fn pop_ready(&mut self) -> ThreadId {
+        let mut best = 0;
+        for (index, id) in self.ready.iter().enumerate() {
+            if self.threads[id].priority > self.threads[&self.ready[best]].priority {
+                best = index;
+            }
+        }
+        self.ready
+            .remove(best)
+            .expect("The idle thread is never blocked")
+    }
+}
+
+/// The global scheduler, only ever accessed with interrupts disabled.
+struct SchedulerCell(UnsafeCell<Option<Scheduler>>);
+
+unsafe impl Sync for SchedulerCell {}
+
+impl SchedulerCell {
+    fn with<R>(&self, f: impl FnOnce(&mut Scheduler) -> R) -> R {
+        processor::critical_section(|| {
+            let scheduler = unsafe { &mut *self.0.get() };
+            f(scheduler.as_mut().expect("Threads aren't initialized"))
+        })
+    }
+}
+
+static SCHEDULER: SchedulerCell = SchedulerCell(UnsafeCell::new(None));
+
+/// Called on the way out of every exception, returns the context of the thread to resume.
+fn schedule(context: &mut Context) -> *mut Context {
+    SCHEDULER.with(|scheduler| {
+        if !scheduler.need_resched {
+            return context as *mut Context;
+        }
+        scheduler.need_resched = false;
+
+        let current = scheduler.current;
+        if scheduler.threads[&current].state == State::Running {
+            scheduler.threads.get_mut(&current).unwrap().state = State::Ready;
+            scheduler.ready.push_back(current);
+        }
+
+        let next = scheduler.pop_ready();
+        scheduler.threads.get_mut(&next).unwrap().state = State::Running;
+        if next == current {
+            return context as *mut Context;
+        }
+
+        // The finished thread still runs on its stack until the context switch, keep it around.
+        let thread = scheduler.threads.get_mut(&current).unwrap();
+        if thread.state == State::Finished {
+            let thread = scheduler.threads.remove(&current).unwrap();
+            scheduler.zombies.extend(thread.stack);
+        } else {
+            thread.context = context;
+            thread.gqr = gqr::save();
+        }
+
+        let thread = &scheduler.threads[&next];
+        gqr::restore(&thread.gqr);
+        scheduler.current = next;
+        thread.context
+    })
+}
+
+/// Free the stacks of the threads which finished since the last call.
+fn reap() {
+    let zombies = SCHEDULER.with(|scheduler| mem::take(&mut scheduler.zombies));
+    drop(zombies);
+}
+
+/// Switch threads if an unpark made a more urgent thread ready.
+///
+/// In exception handlers and critical sections, the switch is left to the scheduler on the way
+/// out of the next exception.
+pub(crate) fn preempt() {
+    let enabled = register::mfmsr() & 0x8000 != 0;
+    if enabled && !exception::in_exception() && SCHEDULER.with(|scheduler| scheduler.need_resched) {
+        exception::reschedule();
+    }
+}
+
+/// Setup threading, with the caller as the main thread.
+///
+/// Called by the runtime once interrupts are enabled.
+pub(crate) fn init() {
+    let mut threads = BTreeMap::new();
+    threads.insert(MAIN, Thread::new(DEFAULT_PRIORITY, State::Running, None));
+    processor::critical_section(|| unsafe {
+        *SCHEDULER.0.get() = Some(Scheduler {
+            threads,
+            ready: VecDeque::new(),
+            current: MAIN,
+            next_id: MAIN.0 + 1,
+            need_resched: false,
+            zombies: Vec::new(),
+        });
+    });
+
+    spawn_raw(IDLE_STACK_SIZE, 0, Box::new(idle));
+
+    unsafe { exception::set_scheduler(schedule) };
+    alarm::set_periodic_alarm(TIME_SLICE, TIME_SLICE, || {
+        SCHEDULER.with(|scheduler| scheduler.need_resched = true)
+    });
+}
+
+/// Body of the idle thread, which runs when every other thread is blocked.
+fn idle() {
+    loop {
+        processor::ppc_doze();
+    }
+}
+
+/// First code run by a new thread, on its own stack.
+extern "C" fn thread_start(main: *mut Box<dyn FnOnce() + Send>) -> ! {
+    let main = unsafe { Box::from_raw(main) };
+    main();
+
+    // Thread-local values may run arbitrary code when dropped, so do it before finishing.
+    let locals = SCHEDULER.with(|scheduler| mem::take(&mut scheduler.current_mut().locals));
+    drop(locals);
+
+    SCHEDULER.with(|scheduler| {
+        scheduler.current_mut().state = State::Finished;
+        scheduler.need_resched = true;
+    });
+    exception::reschedule();
+    unreachable!("A finished thread got resumed");
+}
+
+/// Create a ready thread running ``main``.
+fn spawn_raw(stack_size: usize, priority: u8, main: Box<dyn FnOnce() + Send>) -> ThreadId {
+    reap();
+
+    let mut stack = alloc::vec![0u8; stack_size].into_boxed_slice();
+    let bottom = stack.as_mut_ptr() as usize;
+    let top = (bottom + stack_size) & !0xf;
+
+    // The initial context lives at the top of the stack, below it is the first stack frame.
+    let context = (top - mem::size_of::<Context>()) & !0xf;
+    let sp = context - 16;
+    assert!(sp > bottom, "Thread stack too small");
+
+    // Share the small data areas of the spawning thread.
+    #[cfg(target_arch = "powerpc")]
+    let (r2, r13): (u32, u32);
+    #[cfg(target_arch = "powerpc")]
+    unsafe {
+        asm!("mr {0},2",
+             "mr {1},13",
+            out(reg) r2,
+            out(reg) r13,
+            options(nomem, nostack));
+    }
+    #[cfg(not(target_arch = "powerpc"))]
+    let (r2, r13) = (0, 0);
+
+    let mut initial = Context::default();
+    initial.gpr[1] = sp as u32;
+    initial.gpr[2] = r2;
+    initial.gpr[3] = Box::into_raw(Box::new(main)) as u32;
+    initial.gpr[13] = r13;
+    initial.srr0 = thread_start as *const () as u32;
+    // MSR[EE], and never MSR[POW] even when spawned by the idle thread.
+    initial.srr1 = (register::mfmsr() | 0x8000) & !0x0004_0000;
+    unsafe {
+        core::ptr::write(context as *mut Context, initial);
+        // Null back chain and LR save word.
+        core::ptr::write_bytes(sp as *mut u8, 0, 8);
+    }
+
+    let mut thread = Thread::new(priority, State::Ready, Some(stack));
+    thread.context = context as *mut Context;
+    let id = SCHEDULER.with(|scheduler| {
+        let id = ThreadId(scheduler.next_id);
+        scheduler.next_id += 1;
+        scheduler.threads.insert(id, thread);
+        scheduler.ready.push_back(id);
+        if priority > scheduler.current_mut().priority {
+            scheduler.need_resched = true;
+        }
+        id
+    });
+    preempt();
+    id
+}
+
+/// Result of a thread, shared with its ``JoinHandle``, only ever accessed with interrupts
+/// disabled.
+struct Packet<T> {
+    result: Option<T>,
+    finished: bool,
+    /// Thread waiting in ``join``.
+    joiner: Option<ThreadId>,
+}
+
+struct PacketCell<T>(UnsafeCell<Packet<T>>);
+
+unsafe impl<T: Send> Sync for PacketCell<T> {}
+unsafe impl<T: Send> Send for PacketCell<T> {}
+
+impl<T> PacketCell<T> {
+    fn with<R>(&self, f: impl FnOnce(&mut Packet<T>) -> R) -> R {
+        processor::critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+}
+
+/// Thread factory, to configure the stack size and priority of a new thread.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    stack_size: usize,
+    priority: u8,
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// Create a builder with the default stack size and priority.
+    pub fn new() -> Builder {
+        Builder {
+            stack_size: DEFAULT_STACK_SIZE,
+            priority: DEFAULT_PRIORITY,
+        }
+    }
+
+    /// Set the size of the stack of the new thread, in bytes.
+    pub fn stack_size(mut self, stack_size: usize) -> Builder {
+        self.stack_size = stack_size;
+        self
+    }
+
+    /// Set the priority of the new thread, higher priorities preempt lower ones.
+    ///
+    /// **NOTE**: This function panics if ``priority`` is zero.
+    pub fn priority(mut self, priority: u8) -> Builder {
+        assert!(priority > 0, "Priority 0 is reserved to the idle thread");
+        self.priority = priority;
+        self
+    }
+
+    /// Spawn a new thread running ``f``, returning a handle to join it.
+    ///
+    /// The new thread runs immediately if it is more urgent than the caller.
+    pub fn spawn<F, T>(self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let packet = Arc::new(PacketCell(UnsafeCell::new(Packet {
+            result: None,
+            finished: false,
+            joiner: None,
+        })));
+        let their_packet = packet.clone();
+        let main = move || {
+            let result = f();
+            let joiner = their_packet.with(|packet| {
+                packet.result = Some(result);
+                packet.finished = true;
+                packet.joiner.take()
+            });
+            if let Some(joiner) = joiner {
+                unpark(joiner);
+            }
+        };
+
+        let id = spawn_raw(self.stack_size, self.priority, Box::new(main));
+        JoinHandle { id, packet }
+    }
+}
+
+/// Spawn a new thread running ``f`` with the default stack size and priority.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new().spawn(f)
+}
+
+/// Owned permission to wait for a thread to finish and take its result.
+///
+/// Dropping the handle detaches the thread.
+pub struct JoinHandle<T> {
+    id: ThreadId,
+    packet: Arc<PacketCell<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Get the identifier of the thread.
+    pub fn thread(&self) -> ThreadId {
+        self.id
+    }
+
+    /// Whether the thread has finished running.
+    pub fn is_finished(&self) -> bool {
+        self.packet.with(|packet| packet.finished)
+    }
+
+    /// Block until the thread finishes, and return its result.
+    pub fn join(self) -> T {
+        loop {
+            let result = self.packet.with(|packet| {
+                if !packet.finished {
+                    packet.joiner = Some(current());
+                }
+                packet.result.take()
+            });
+            if let Some(result) = result {
+                reap();
+                return result;
+            }
+            park();
+        }
+    }
+}
+
+/// Get the identifier of the calling thread.
+pub fn current() -> ThreadId {
+    SCHEDULER.with(|scheduler| scheduler.current)
+}
+
+/// Get the priority of the calling thread.
+pub fn priority() -> u8 {
+    SCHEDULER.with(|scheduler| scheduler.current_mut().priority)
+}
+
+/// Change the priority of the calling thread, letting more urgent threads run.
+///
+/// **NOTE**: This function panics if ``priority`` is zero, or if called from an exception
+/// handler.
+pub fn set_priority(priority: u8) {
+    assert!(
+        !exception::in_exception(),
+        "Can't block in an exception handler"
+    );
+    assert!(priority > 0, "Priority 0 is reserved to the idle thread");
+    SCHEDULER.with(|scheduler| {
+        scheduler.current_mut().priority = priority;
+        scheduler.need_resched = true;
+    });
+    exception::reschedule();
+}
+
+/// Let the other ready threads of the same priority run.
+///
+/// **NOTE**: This function panics if called from an exception handler.
+pub fn yield_now() {
+    assert!(
+        !exception::in_exception(),
+        "Can't block in an exception handler"
+    );
+    SCHEDULER.with(|scheduler| scheduler.need_resched = true);
+    exception::reschedule();
+}
+
+/// Block the calling thread until another thread or an interrupt handler unparks it.
+///
+/// Returns immediately if the thread got unparked since the last call, and may also return
+/// spuriously.
+///
+/// **NOTE**: This function panics if called from an exception handler.
+pub fn park() {
+    assert!(
+        !exception::in_exception(),
+        "Can't block in an exception handler"
+    );
+    let block = SCHEDULER.with(|scheduler| {
+        let thread = scheduler.current_mut();
+        if mem::take(&mut thread.unparked) {
+            false
+        } else {
+            thread.state = State::Blocked;
+            scheduler.need_resched = true;
+            true
+        }
+    });
+    if block {
+        exception::reschedule();
+    }
+}
+
+/// Wake up the given thread if it is parked, otherwise make its next ``park`` return.
+///
+/// May be called from interrupt handlers.
+pub fn unpark(id: ThreadId) {
+    SCHEDULER.with(|scheduler| scheduler.unpark(id));
+    preempt();
+}
+
+/// Block the calling thread for at least ``duration``, letting the other threads run.
+///
+/// **NOTE**: This function panics if called from an exception handler.
+pub fn sleep(duration: Duration) {
+    assert!(
+        !exception::in_exception(),
+        "Can't block in an exception handler"
+    );
+    let deadline = Instant::now() + duration;
+    let id = current();
+    let alarm = alarm::set_alarm(duration, move || unpark(id));
+    while Instant::now() < deadline {
+        park();
+    }
+    alarm::cancel_alarm(alarm);
+}
+
+/// A queue of threads blocked until some condition holds, the building block of ``sync``.
+pub struct WaitQueue(UnsafeCell<Vec<ThreadId>>);
+
+unsafe impl Sync for WaitQueue {}
+
+impl Default for WaitQueue {
+    fn default() -> WaitQueue {
+        WaitQueue::new()
+    }
+}
+
+impl WaitQueue {
+    /// Create an empty queue.
+    pub const fn new() -> WaitQueue {
+        WaitQueue(UnsafeCell::new(Vec::new()))
+    }
+
+    /// Block until ``condition`` returns ``Some``, and return its value.
+    ///
+    /// ``condition`` is called with interrupts disabled, first right away, then every time the
+    /// thread gets woken up, possibly spuriously.
+    ///
+    /// **NOTE**: This function panics if called from an exception handler.
+    pub fn wait_until<R>(&self, condition: impl FnMut() -> Option<R>) -> R {
+        self.wait(None, condition).unwrap()
+    }
+
+    /// Same as ``wait_until``, but give up once ``timeout`` has elapsed, returning ``None``.
+    pub fn wait_until_timeout<R>(
+        &self,
+        timeout: Duration,
+        condition: impl FnMut() -> Option<R>,
+    ) -> Option<R> {
+        self.wait(Some(Instant::now() + timeout), condition)
+    }
+
+    fn wait<R>(
+        &self,
+        deadline: Option<Instant>,
+        mut condition: impl FnMut() -> Option<R>,
+    ) -> Option<R> {
+        assert!(
+            !exception::in_exception(),
+            "Can't block in an exception handler"
+        );
+
+        let id = current();
+        let alarm = deadline.map(|deadline| alarm::set_alarm_at(deadline, move || unpark(id)));
+        let result = loop {
+            let result = processor::critical_section(|| {
+                let waiters = unsafe { &mut *self.0.get() };
+                waiters.retain(|&waiter| waiter != id);
+                if let Some(result) = condition() {
+                    return Some(Some(result));
+                }
+                match deadline {
+                    Some(deadline) if Instant::now() >= deadline => Some(None),
+                    _ => {
+                        waiters.push(id);
+                        None
+                    }
+                }
+            });
+            match result {
+                Some(result) => break result,
+                None => park(),
+            }
+        };
+        if let Some(alarm) = alarm {
+            alarm::cancel_alarm(alarm);
+        }
+        result
+    }
+
+    /// Join the queue and block until ``notify_one`` or ``notify_all`` wakes the calling thread
+    /// up, or until ``timeout`` has elapsed.  Returns whether it got notified.
+    ///
+    /// ``queued`` is called with interrupts disabled once the thread is in the queue, so that a
+    /// notification sent right after it can't get lost.
+    ///
+    /// **NOTE**: This function panics if called from an exception handler.
+    pub fn wait_notified(&self, timeout: Option<Duration>, queued: impl FnOnce()) -> bool {
+        assert!(
+            !exception::in_exception(),
+            "Can't block in an exception handler"
+        );
+
+        let id = current();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let alarm = deadline.map(|deadline| alarm::set_alarm_at(deadline, move || unpark(id)));
+        processor::critical_section(|| {
+            unsafe { &mut *self.0.get() }.push(id);
+            queued();
+        });
+        let notified = loop {
+            let notified = processor::critical_section(|| {
+                let waiters = unsafe { &mut *self.0.get() };
+                poll_notified(waiters, id, deadline, Instant::now())
+            });
+            match notified {
+                Some(notified) => break notified,
+                None => park(),
+            }
+        };
+        if let Some(alarm) = alarm {
+            alarm::cancel_alarm(alarm);
+        }
+        notified
+    }
+
+    /// Wake up the thread which waited the longest, returns whether there was one.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn notify_one(&self) -> bool {
+        let woken = processor::critical_section(|| {
+            let waiters = unsafe { &mut *self.0.get() };
+            if waiters.is_empty() {
+                return false;
+            }
+            unpark(waiters.remove(0));
+            true
+        });
+        preempt();
+        woken
+    }
+
+    /// Wake up every waiting thread, returns how many there were.
+    ///
+    /// May be called from interrupt handlers.
+    pub fn notify_all(&self) -> usize {
+        let woken = processor::critical_section(|| {
+            let waiters = mem::take(unsafe { &mut *self.0.get() });
+            for &waiter in waiters.iter() {
+                unpark(waiter);
+            }
+            waiters.len()
+        });
+        preempt();
+        woken
+    }
+}
+
+/// Check on a thread waiting in ``waiters`` for a notification, which takes it out of the queue.
+///
+/// Returns ``Some(true)`` once notified, ``Some(false)`` after leaving the queue if ``deadline``
+/// has passed, or ``None`` if it has to keep waiting.
+fn poll_notified(
+    waiters: &mut Vec<ThreadId>,
+    id: ThreadId,
+    deadline: Option<Instant>,
+    now: Instant,
+) -> Option<bool> {
+    let index = match waiters.iter().position(|&waiter| waiter == id) {
+        Some(index) => index,
+        None => return Some(true),
+    };
+    match deadline {
+        Some(deadline) if now >= deadline => {
+            waiters.remove(index);
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+/// A key to a thread-local value, declared with ``thread_local!``.
+///
+/// Each thread lazily initializes its own value on first access, which gets dropped when the
+/// thread finishes.
+pub struct LocalKey<T: 'static> {
+    #[doc(hidden)]
+    pub __init: fn() -> T,
+}
+
+impl<T: 'static> LocalKey<T> {
+    /// Call ``f`` with a reference to the value of the calling thread.
+    pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+        let key = self as *const LocalKey<T> as usize;
+        let find = |scheduler: &mut Scheduler| {
+            let value = scheduler.current_mut().locals.get(&key)?;
+            Some(value.downcast_ref::<T>().unwrap() as *const T)
+        };
+
+        let value = match SCHEDULER.with(find) {
+            Some(value) => value,
+            None => {
+                // The initializer runs without the scheduler locked, it may access other keys.
+                let value = Box::new((self.__init)());
+                let pointer = &*value as *const T;
+                SCHEDULER.with(|scheduler| scheduler.current_mut().locals.insert(key, value));
+                pointer
+            }
+        };
+
+        // Values are boxed, and only dropped by their thread once it is done with them.
+        f(unsafe { &*value })
+    }
+}
+
+/// Declare thread-local statics, of type ``LocalKey``.
+#[macro_export]
+macro_rules! thread_local {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::thread::LocalKey<$t> = {
+                fn __init() -> $t {
+                    $init
+                }
+                $crate::thread::LocalKey { __init }
+            };
+        )*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notified_once_out_of_the_queue() {
+        let mut waiters = alloc::vec![ThreadId(2)];
+        let deadline = Some(Instant::from_ticks(100));
+        assert_eq!(
+            poll_notified(&mut waiters, ThreadId(1), None, Instant::from_ticks(0)),
+            Some(true)
+        );
+        assert_eq!(
+            poll_notified(
+                &mut waiters,
+                ThreadId(1),
+                deadline,
+                Instant::from_ticks(200)
+            ),
+            Some(true)
+        );
+        assert_eq!(waiters, [ThreadId(2)]);
+    }
+
+    #[test]
+    fn keep_waiting_before_the_deadline() {
+        // The alarm unparking the thread early must not count as a notification.
+        let mut waiters = alloc::vec![ThreadId(1), ThreadId(2)];
+        let deadline = Some(Instant::from_ticks(100));
+        assert_eq!(
+            poll_notified(&mut waiters, ThreadId(1), deadline, Instant::from_ticks(99)),
+            None
+        );
+        assert_eq!(
+            poll_notified(&mut waiters, ThreadId(2), None, Instant::from_ticks(200)),
+            None
+        );
+        assert_eq!(waiters, [ThreadId(1), ThreadId(2)]);
+    }
+
+    #[test]
+    fn time_out_at_the_deadline() {
+        let mut waiters = alloc::vec![ThreadId(1), ThreadId(2), ThreadId(3)];
+        let deadline = Some(Instant::from_ticks(100));
+        assert_eq!(
+            poll_notified(
+                &mut waiters,
+                ThreadId(2),
+                deadline,
+                Instant::from_ticks(100)
+            ),
+            Some(false)
+        );
+        assert_eq!(waiters, [ThreadId(1), ThreadId(3)]);
+    }
+}